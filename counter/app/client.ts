@@ -27,9 +27,14 @@ import { BN } from "bn.js";
 
   const counterAccount = anchor.web3.Keypair.generate();
   const account2 = anchor.web3.Keypair.generate()
-  const signature = await program.rpc.init({
+  const [historyAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from("history"), counterAccount.publicKey.toBuffer()],
+    Program_ID
+  );
+  const signature = await program.rpc.init({ min: -5, max: 10, maxStep: 10 }, new BN(0), false, {
     accounts: {
       computeAccount: counterAccount.publicKey,
+      history: historyAccount,
       user: provider.wallet.publicKey,
       authority: provider.wallet.publicKey,
       systemProgram: anchor.web3.SystemProgram.programId,
@@ -54,6 +59,7 @@ import { BN } from "bn.js";
     {
     accounts: {
       computeAccount: counterAccount.publicKey,
+      history: historyAccount,
       authority: account2.publicKey,
     },
     signers: [account2]
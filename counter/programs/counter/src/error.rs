@@ -4,14 +4,95 @@ use anchor_lang::prelude::*;
 
 #[error_code]
 pub enum ErrorDefine {
-  #[msg("Minimul total is -5")]
+  #[msg("Total would go below the counter minimum")]
   SubError,
 
-  #[msg("Maximum total is 10")]
+  #[msg("Total would go above the counter maximum")]
   AddError,
 
   #[msg("Unauthorized !!!")]
   Unauthorized,
+
+  #[msg("Bounds need min <= max and a positive max step")]
+  InvalidBounds,
+
+  #[msg("Current total is outside the given bounds")]
+  TotalOutOfBounds,
+
+  #[msg("Value must be between 0 and the counter max step")]
+  InvalidStep,
+
+  #[msg("No authority change is pending")]
+  NoPendingAuthority,
+
+  #[msg("Signer is not the pending authority")]
+  PendingAuthorityMismatch,
+
+  #[msg("Authority proposal has expired")]
+  AuthorityProposalExpired,
+
+  #[msg("Multisig needs unique signers, at most 5, and a threshold no larger than the signer count")]
+  InvalidMultisig,
+
+  #[msg("Not enough multisig signers")]
+  NotEnoughSigners,
+
+  #[msg("Counter total overflowed")]
+  Overflow,
+
+  #[msg("Account is not a counter in the legacy 64 byte layout")]
+  NotLegacyAccount,
+
+  #[msg("Counter total is not zero")]
+  TotalNotZero,
+
+  #[msg("Batch needs between 1 and 16 operations")]
+  InvalidBatch,
+
+  #[msg("Operator grant has expired")]
+  OperatorExpired,
+
+  #[msg("Operator has used up its quota")]
+  OperatorQuotaExceeded,
+
+  #[msg("Sequence name is longer than 32 bytes")]
+  SequenceNameTooLong,
+
+  #[msg("Counter is sharded, use the shard instructions")]
+  CounterIsSharded,
+
+  #[msg("Invalid shard count, index or shard accounts")]
+  InvalidShards,
+
+  #[msg("Shard reservation used up, aggregate to rebalance")]
+  ShardReservationExceeded,
+
+  #[msg("Rate limit window needs at least one slot")]
+  InvalidRateLimit,
+
+  #[msg("Counter rate limit exceeded for the sliding window")]
+  RateLimitExceeded,
+
+  #[msg("Hook needs at most 4 thresholds and cannot be this program")]
+  InvalidHook,
+
+  #[msg("Hook program is missing from the remaining accounts")]
+  HookProgramMissing,
+
+  #[msg("Reset window needs a positive period and a baseline inside the bounds")]
+  InvalidResetWindow,
+
+  #[msg("Counter resets per window and cannot be sharded")]
+  CounterIsWindowed,
+
+  #[msg("Counter is rate limited and cannot be sharded")]
+  CounterIsRateLimited,
+
+  #[msg("Revoke every operator before closing the counter")]
+  OperatorsRemain,
+
+  #[msg("Hook signer PDA is missing from the remaining accounts")]
+  HookSignerMissing,
 }
 
 impl From<ErrorDefine> for ProgramError {
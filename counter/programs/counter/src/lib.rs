@@ -1,55 +1,756 @@
 pub mod error;
+pub mod processor;
+pub mod state;
 
 use crate::error::ErrorDefine;
+use crate::processor::{
+    apply_add, apply_op, apply_shard_add, apply_shard_sub, apply_sub, check_authority, fire_hooks,
+    load_shard, next_reset_at, read_legacy_counter, record_authority_change, record_history,
+    reserve_shard, roll_window, transfer_sol, use_operator_quota,
+};
+use crate::state::{
+    Bounds, ComputeAccount, CounterOp, History, Multisig, Operation, Operator, RateLimit,
+    ResetKind, ResetWindow, Sequence, Shard, Snapshot, MAX_BATCH_OPS, MAX_MULTISIG_SIGNERS,
+    MAX_SEQUENCE_NAME_LEN, MAX_SHARDS, MAX_THRESHOLDS,
+};
 use anchor_lang::prelude::*;
 
 declare_id!("4UW83kGzjovz2gujn87fcDG7oEhjp43VgjHvaoAaCtPc");
 
+const HISTORY_PDA_SEED: &[u8] = b"history";
+const HOOK_PDA_SEED: &[u8] = b"hook";
+const OPERATOR_PDA_SEED: &[u8] = b"operator";
+const SEQUENCE_PDA_SEED: &[u8] = b"sequence";
+const SHARD_PDA_SEED: &[u8] = b"shard";
+const SNAPSHOT_PDA_SEED: &[u8] = b"snapshot";
+
 #[program]
 pub mod counter_app {
     use super::*;
 
-    pub fn init(ctx: Context<Create>) -> Result<()> {
+    // `initial_total` lets a counter start inside ranges that leave out 0, like [1, 100]
+    pub fn init(
+        ctx: Context<Create>,
+        bounds: Bounds,
+        initial_total: i64,
+        close_only_if_zero: bool,
+    ) -> Result<()> {
+        require!(bounds.is_valid(), ErrorDefine::InvalidBounds);
+        require!(
+            bounds.contains(initial_total),
+            ErrorDefine::TotalOutOfBounds
+        );
         let counter_account = &mut ctx.accounts.compute_account;
         let authority = *ctx.accounts.authority.key;
-        counter_account.total = 0;
+        counter_account.total = initial_total;
         counter_account.authority = authority;
+        counter_account.bounds = bounds;
+        counter_account.pending_authority = None;
+        counter_account.pending_expiry_slot = None;
+        counter_account.multisig = Multisig::default();
+        counter_account.shard_count = 0;
+        counter_account.rate_limit = None;
+        counter_account.window_start_slot = 0;
+        counter_account.window_calls = 0;
+        counter_account.window_change = 0;
+        counter_account.prev_window_calls = 0;
+        counter_account.prev_window_change = 0;
+        counter_account.snapshot_count = 0;
+        counter_account.hook_program = None;
+        counter_account.thresholds = Vec::new();
+        counter_account.reset_window = None;
+        counter_account.reset_at = 0;
+        counter_account.last_window_total = 0;
+        counter_account.operator_count = 0;
+        counter_account.close_only_if_zero = close_only_if_zero;
+
+        let history = &mut ctx.accounts.history;
+        history.compute_account = counter_account.key();
+        history.head = 0;
+        history.entries = Vec::new();
+        history.bump = *ctx.bumps.get("history").unwrap();
         Ok(())
     }
 
-    pub fn change_authority(ctx: Context<ChangeAuthor>, new_auth: Pubkey) -> Result<()> {
-        ctx.accounts.compute_account.authority = new_auth;
+    // move a counter created with the 64 byte `i8` layout to the current one
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let compute_account = ctx.accounts.compute_account.to_account_info();
+        let (total, authority) = read_legacy_counter(&compute_account.try_borrow_data()?)?;
+        require_keys_eq!(
+            authority,
+            ctx.accounts.authority.key(),
+            ErrorDefine::Unauthorized
+        );
+
+        // top up rent before growing the account
+        let rent = Rent::get()?.minimum_balance(ComputeAccount::LEN);
+        let lamports = compute_account.lamports();
+        if rent > lamports {
+            transfer_sol(
+                ctx.accounts.user.to_account_info(),
+                compute_account.clone(),
+                rent - lamports,
+                ctx.accounts.system_program.to_account_info(),
+            )?;
+        }
+        compute_account.realloc(ComputeAccount::LEN, true)?;
+        let migrated = ComputeAccount {
+            total,
+            authority,
+            bounds: Bounds::LEGACY,
+            pending_authority: None,
+            pending_expiry_slot: None,
+            multisig: Multisig::default(),
+            shard_count: 0,
+            rate_limit: None,
+            window_start_slot: 0,
+            window_calls: 0,
+            window_change: 0,
+            prev_window_calls: 0,
+            prev_window_change: 0,
+            snapshot_count: 0,
+            hook_program: None,
+            thresholds: Vec::new(),
+            reset_window: None,
+            reset_at: 0,
+            last_window_total: 0,
+            operator_count: 0,
+            close_only_if_zero: false,
+        };
+        migrated.try_serialize(&mut &mut compute_account.try_borrow_mut_data()?[..])?;
+
+        // legacy counters have no history yet
+        let history = &mut ctx.accounts.history;
+        history.compute_account = compute_account.key();
+        history.head = 0;
+        history.entries = Vec::new();
+        history.bump = *ctx.bumps.get("history").unwrap();
         Ok(())
     }
 
-    pub fn add(ctx: Context<Add>, value: i8) -> Result<()> {
+    // counter and history are closed to `destination` once this returns. Operators have
+    // to be revoked first, shards of a sharded counter are passed in index order as
+    // writable remaining accounts and closed here as well. See `set_close_policy`.
+    pub fn close<'info>(ctx: Context<'_, '_, '_, 'info, Close<'info>>) -> Result<()> {
         let compute_account = &mut ctx.accounts.compute_account;
-        // check conditions
-        require_gte!(10, compute_account.total + value, ErrorDefine::AddError);
-        compute_account.total += value;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // an ended window counts as reset to its baseline
+        roll_window(compute_account)?;
+        // a counter re-created at the same address must not pick up old grants
+        require_eq!(
+            compute_account.operator_count,
+            0,
+            ErrorDefine::OperatorsRemain
+        );
+        // multisig approvals are the signers among the remaining accounts
+        let shard_infos: Vec<&AccountInfo<'info>> = ctx
+            .remaining_accounts
+            .iter()
+            .filter(|account| !account.is_signer)
+            .collect();
+        require_eq!(
+            shard_infos.len(),
+            compute_account.shard_count as usize,
+            ErrorDefine::InvalidShards
+        );
+        let mut shards = Vec::new();
+        let mut total = compute_account.total;
+        for (index, shard_info) in shard_infos.into_iter().enumerate() {
+            let shard = load_shard(compute_account.key(), shard_info, index as u8)?;
+            total = total
+                .checked_add(shard.delta)
+                .ok_or(ErrorDefine::Overflow)?;
+            shards.push(shard);
+        }
+        if compute_account.close_only_if_zero {
+            require_eq!(total, 0, ErrorDefine::TotalNotZero);
+        }
+        for shard in shards {
+            shard.close(ctx.accounts.destination.to_account_info())?;
+        }
         Ok(())
     }
 
-    pub fn sub(ctx: Context<Sub>, value: i8) -> Result<()> {
+    pub fn set_bounds(ctx: Context<SetBounds>, bounds: Bounds) -> Result<()> {
         let compute_account = &mut ctx.accounts.compute_account;
-        // check conditions
-        require_gte!(-5, compute_account.total - value, ErrorDefine::SubError);
-        compute_account.total -= value;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // shard reservations are computed from the bounds
+        require_eq!(
+            compute_account.shard_count,
+            0,
+            ErrorDefine::CounterIsSharded
+        );
+        require!(bounds.is_valid(), ErrorDefine::InvalidBounds);
+        if let Some(reset_window) = compute_account.reset_window {
+            require!(
+                bounds.contains(reset_window.baseline),
+                ErrorDefine::InvalidResetWindow
+            );
+        }
+        // the current total has to stay reachable under the new range
+        require!(
+            bounds.contains(compute_account.total),
+            ErrorDefine::TotalOutOfBounds
+        );
+        compute_account.bounds = bounds;
+        Ok(())
+    }
+
+    // `only_if_zero` keeps `close` from dropping a counter that still holds a total
+    pub fn set_close_policy(ctx: Context<SetClosePolicy>, only_if_zero: bool) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        compute_account.close_only_if_zero = only_if_zero;
+        Ok(())
+    }
+
+    // `None` removes the limit, any change drops the usage counted so far, see `RateLimit`
+    pub fn set_rate_limit(ctx: Context<SetRateLimit>, rate_limit: Option<RateLimit>) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // shard writes never go through the parent window
+        require_eq!(
+            compute_account.shard_count,
+            0,
+            ErrorDefine::CounterIsSharded
+        );
+        if let Some(rate_limit) = rate_limit {
+            require_gt!(rate_limit.window_slots, 0, ErrorDefine::InvalidRateLimit);
+        }
+        compute_account.rate_limit = rate_limit;
+        compute_account.window_start_slot = 0;
+        compute_account.window_calls = 0;
+        compute_account.window_change = 0;
+        compute_account.prev_window_calls = 0;
+        compute_account.prev_window_change = 0;
+        Ok(())
+    }
+
+    // `None` turns it back into a plain counter, otherwise the first window starts now
+    pub fn set_reset_window(
+        ctx: Context<SetResetWindow>,
+        reset_window: Option<ResetWindow>,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // shards never roll the parent window
+        require_eq!(
+            compute_account.shard_count,
+            0,
+            ErrorDefine::CounterIsSharded
+        );
+        if let Some(reset_window) = reset_window {
+            if let ResetKind::Seconds { period } = reset_window.kind {
+                require_gt!(period, 0, ErrorDefine::InvalidResetWindow);
+            }
+            require!(
+                compute_account.bounds.contains(reset_window.baseline),
+                ErrorDefine::InvalidResetWindow
+            );
+            let clock = Clock::get()?;
+            compute_account.reset_at =
+                next_reset_at(&reset_window, clock.unix_timestamp as u64, &clock);
+        }
+        compute_account.reset_window = reset_window;
+        Ok(())
+    }
+
+    // hook accounts, including the `[HOOK_PDA_SEED, compute_account]` signer PDA, are passed
+    // as remaining accounts of the add/sub call, see `fire_hooks`.
+    // On a sharded counter hooks fire from `aggregate`, when the shards reach `total`.
+    pub fn set_hook(
+        ctx: Context<SetHook>,
+        hook_program: Option<Pubkey>,
+        thresholds: Vec<i64>,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        require_gte!(MAX_THRESHOLDS, thresholds.len(), ErrorDefine::InvalidHook);
+        // a hook program calling back into the counter would be reentrancy
+        require!(hook_program != Some(crate::ID), ErrorDefine::InvalidHook);
+        compute_account.hook_program = hook_program;
+        compute_account.thresholds = thresholds;
+        Ok(())
+    }
+
+    // threshold 0 switches back to single authority mode
+    pub fn set_multisig(
+        ctx: Context<SetMultisig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        require_gte!(
+            MAX_MULTISIG_SIGNERS,
+            signers.len(),
+            ErrorDefine::InvalidMultisig
+        );
+        require_gte!(
+            signers.len(),
+            threshold as usize,
+            ErrorDefine::InvalidMultisig
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(!signers[..i].contains(signer), ErrorDefine::InvalidMultisig);
+        }
+        compute_account.multisig = if threshold == 0 {
+            Multisig::default()
+        } else {
+            Multisig { signers, threshold }
+        };
+        Ok(())
+    }
+
+    pub fn change_authority(
+        ctx: Context<ChangeAuthor>,
+        new_auth: Pubkey,
+        expires_in_slots: Option<u64>,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // only propose here, `new_auth` has to sign `accept_authority` to take over
+        compute_account.pending_authority = Some(new_auth);
+        compute_account.pending_expiry_slot = match expires_in_slots {
+            Some(slots) => Some(Clock::get()?.slot.saturating_add(slots)),
+            None => None,
+        };
+        record_authority_change(
+            &mut ctx.accounts.history,
+            Operation::ChangeAuthority,
+            compute_account.total,
+            ctx.accounts.authority.key(),
+            new_auth,
+        )?;
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthor>) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        let new_authority = ctx.accounts.new_authority.key();
+        match compute_account.pending_authority {
+            Some(pending) => require_keys_eq!(
+                pending,
+                new_authority,
+                ErrorDefine::PendingAuthorityMismatch
+            ),
+            None => return Err(ErrorDefine::NoPendingAuthority.into()),
+        }
+        if let Some(expiry_slot) = compute_account.pending_expiry_slot {
+            require_gte!(
+                expiry_slot,
+                Clock::get()?.slot,
+                ErrorDefine::AuthorityProposalExpired
+            );
+        }
+        compute_account.authority = new_authority;
+        compute_account.pending_authority = None;
+        compute_account.pending_expiry_slot = None;
+        record_authority_change(
+            &mut ctx.accounts.history,
+            Operation::AcceptAuthority,
+            compute_account.total,
+            new_authority,
+            new_authority,
+        )?;
+        Ok(())
+    }
+
+    pub fn cancel_authority_change(ctx: Context<ChangeAuthor>) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        require!(
+            compute_account.pending_authority.is_some(),
+            ErrorDefine::NoPendingAuthority
+        );
+        compute_account.pending_authority = None;
+        compute_account.pending_expiry_slot = None;
+        Ok(())
+    }
+
+    pub fn add<'info>(ctx: Context<'_, '_, '_, 'info, Add<'info>>, value: i64) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        roll_window(compute_account)?;
+        let old_total = compute_account.total;
+        apply_add(compute_account, value)?;
+        record_history(
+            &mut ctx.accounts.history,
+            Operation::Add,
+            value,
+            compute_account.total,
+            ctx.accounts.authority.key(),
+        )?;
+        fire_hooks(compute_account, old_total, ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    pub fn sub<'info>(ctx: Context<'_, '_, '_, 'info, Sub<'info>>, value: i64) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        roll_window(compute_account)?;
+        let old_total = compute_account.total;
+        apply_sub(compute_account, value)?;
+        record_history(
+            &mut ctx.accounts.history,
+            Operation::Sub,
+            value,
+            compute_account.total,
+            ctx.accounts.authority.key(),
+        )?;
+        fire_hooks(compute_account, old_total, ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    // bounds are checked after every step, one failing step rejects the whole batch
+    pub fn batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, Batch<'info>>,
+        ops: Vec<CounterOp>,
+    ) -> Result<()> {
+        require!(
+            !ops.is_empty() && ops.len() <= MAX_BATCH_OPS,
+            ErrorDefine::InvalidBatch
+        );
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        let signer = ctx.accounts.authority.key();
+        // the clock does not move within a transaction, one roll covers every op
+        roll_window(compute_account)?;
+        for op in ops {
+            let old_total = compute_account.total;
+            let (operation, value) = apply_op(compute_account, op)?;
+            record_history(
+                &mut ctx.accounts.history,
+                operation,
+                value,
+                compute_account.total,
+                signer,
+            )?;
+            fire_hooks(compute_account, old_total, ctx.remaining_accounts)?;
+        }
+        Ok(())
+    }
+
+    // operators can add/sub but never touch authority, bounds or multisig
+    pub fn grant_operator(
+        ctx: Context<GrantOperator>,
+        operator: Pubkey,
+        cap: Option<u64>,
+        expiry_slot: Option<u64>,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        compute_account.operator_count = compute_account
+            .operator_count
+            .checked_add(1)
+            .ok_or(ErrorDefine::Overflow)?;
+        let operator_account = &mut ctx.accounts.operator_account;
+        operator_account.compute_account = ctx.accounts.compute_account.key();
+        operator_account.operator = operator;
+        operator_account.cap = cap;
+        operator_account.used = 0;
+        operator_account.expiry_slot = expiry_slot;
+        operator_account.bump = *ctx.bumps.get("operator_account").unwrap();
+        Ok(())
+    }
+
+    // operator account is closed to `destination` once this returns
+    pub fn revoke_operator(ctx: Context<RevokeOperator>) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        compute_account.operator_count = compute_account.operator_count.saturating_sub(1);
+        Ok(())
+    }
+
+    pub fn operator_add<'info>(
+        ctx: Context<'_, '_, '_, 'info, OperatorMutate<'info>>,
+        value: i64,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        use_operator_quota(&mut ctx.accounts.operator_account, value)?;
+        roll_window(compute_account)?;
+        let old_total = compute_account.total;
+        apply_add(compute_account, value)?;
+        record_history(
+            &mut ctx.accounts.history,
+            Operation::Add,
+            value,
+            compute_account.total,
+            ctx.accounts.operator.key(),
+        )?;
+        fire_hooks(compute_account, old_total, ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    pub fn operator_sub<'info>(
+        ctx: Context<'_, '_, '_, 'info, OperatorMutate<'info>>,
+        value: i64,
+    ) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        use_operator_quota(&mut ctx.accounts.operator_account, value)?;
+        roll_window(compute_account)?;
+        let old_total = compute_account.total;
+        apply_sub(compute_account, value)?;
+        record_history(
+            &mut ctx.accounts.history,
+            Operation::Sub,
+            value,
+            compute_account.total,
+            ctx.accounts.operator.key(),
+        )?;
+        fire_hooks(compute_account, old_total, ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    pub fn init_sequence(ctx: Context<InitSequence>, name: String) -> Result<()> {
+        require_gte!(
+            MAX_SEQUENCE_NAME_LEN,
+            name.len(),
+            ErrorDefine::SequenceNameTooLong
+        );
+        let sequence = &mut ctx.accounts.sequence;
+        sequence.owner = ctx.accounts.owner.key();
+        sequence.name = name;
+        sequence.value = 0;
+        sequence.bump = *ctx.bumps.get("sequence").unwrap();
         Ok(())
     }
-}
 
-#[account]
-pub struct ComputeAccount {
-    pub total: i8,
-    pub authority: Pubkey,
+    // the new id is also set as return data, so callers can CPI in to allocate ids
+    pub fn next_id(ctx: Context<NextId>) -> Result<u64> {
+        let sequence = &mut ctx.accounts.sequence;
+        sequence.value = sequence.value.checked_add(1).ok_or(ErrorDefine::Overflow)?;
+        Ok(sequence.value)
+    }
+
+    // after this `add` / `sub` are rejected, writes go through `shard_add` / `shard_sub`.
+    // Those per-shard writes are not recorded in history, only each `aggregate` fold is.
+    pub fn enable_sharding(ctx: Context<EnableSharding>, shard_count: u8) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        require_eq!(
+            compute_account.shard_count,
+            0,
+            ErrorDefine::CounterIsSharded
+        );
+        require!(
+            compute_account.reset_window.is_none(),
+            ErrorDefine::CounterIsWindowed
+        );
+        require!(
+            compute_account.rate_limit.is_none(),
+            ErrorDefine::CounterIsRateLimited
+        );
+        require!(
+            (1..=MAX_SHARDS).contains(&shard_count),
+            ErrorDefine::InvalidShards
+        );
+        compute_account.shard_count = shard_count;
+        Ok(())
+    }
+
+    pub fn init_shard(ctx: Context<InitShard>, index: u8) -> Result<()> {
+        let compute_account = &ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        require_gt!(
+            compute_account.shard_count,
+            index,
+            ErrorDefine::InvalidShards
+        );
+        let shard = &mut ctx.accounts.shard;
+        shard.compute_account = compute_account.key();
+        shard.index = index;
+        shard.delta = 0;
+        shard.bump = *ctx.bumps.get("shard").unwrap();
+        reserve_shard(compute_account, shard);
+        Ok(())
+    }
+
+    // shard writes only lock the shard, the parent counter is read only
+    pub fn shard_add(ctx: Context<ShardMutate>, value: i64) -> Result<()> {
+        let compute_account = &ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        apply_shard_add(compute_account, &mut ctx.accounts.shard, value)
+    }
+
+    pub fn shard_sub(ctx: Context<ShardMutate>, value: i64) -> Result<()> {
+        let compute_account = &ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        apply_shard_sub(compute_account, &mut ctx.accounts.shard, value)
+    }
+
+    // fold every shard into `total` and hand out fresh reservations, shards are passed
+    // in index order as the first writable remaining accounts, hook accounts follow them
+    pub fn aggregate<'info>(ctx: Context<'_, '_, '_, 'info, Aggregate<'info>>) -> Result<i64> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        let shard_count = compute_account.shard_count as usize;
+        require_gt!(shard_count, 0, ErrorDefine::InvalidShards);
+        require_gte!(
+            ctx.remaining_accounts.len(),
+            shard_count,
+            ErrorDefine::InvalidShards
+        );
+        let (shard_infos, hook_accounts) = ctx.remaining_accounts.split_at(shard_count);
+        let old_total = compute_account.total;
+        let mut shards = Vec::new();
+        let mut total = compute_account.total;
+        for (index, shard_info) in shard_infos.iter().enumerate() {
+            let shard = load_shard(compute_account.key(), shard_info, index as u8)?;
+            total = total
+                .checked_add(shard.delta)
+                .ok_or(ErrorDefine::Overflow)?;
+            shards.push(shard);
+        }
+        compute_account.total = total;
+        // nobody signs `aggregate`, the entry carries the default key
+        record_history(
+            &mut ctx.accounts.history,
+            Operation::Aggregate,
+            total.checked_sub(old_total).ok_or(ErrorDefine::Overflow)?,
+            total,
+            Pubkey::default(),
+        )?;
+        for mut shard in shards {
+            shard.delta = 0;
+            reserve_shard(compute_account, &mut shard);
+            shard.exit(ctx.program_id)?;
+        }
+        // shard writes leave the parent untouched, thresholds are checked on the fold
+        fire_hooks(compute_account, old_total, hook_accounts)?;
+        Ok(total)
+    }
+
+    pub fn snapshot(ctx: Context<TakeSnapshot>) -> Result<()> {
+        let compute_account = &mut ctx.accounts.compute_account;
+        check_authority(
+            compute_account,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+        )?;
+        // snapshot the total of the current window, not of one that already ended
+        roll_window(compute_account)?;
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.compute_account = compute_account.key();
+        snapshot.index = compute_account.snapshot_count;
+        snapshot.total = compute_account.total;
+        snapshot.authority = compute_account.authority;
+        snapshot.slot = Clock::get()?.slot;
+        snapshot.bump = *ctx.bumps.get("snapshot").unwrap();
+        compute_account.snapshot_count = compute_account
+            .snapshot_count
+            .checked_add(1)
+            .ok_or(ErrorDefine::Overflow)?;
+        Ok(())
+    }
+
+    // snapshot is closed to `destination` once this returns. While the counter exists
+    // its authority decides, once it is closed the authority stored on the snapshot does.
+    pub fn close_snapshot(ctx: Context<CloseSnapshot>) -> Result<()> {
+        let compute_account = ctx.accounts.compute_account.to_account_info();
+        if *compute_account.owner == crate::ID && !compute_account.data_is_empty() {
+            let compute_account: Account<ComputeAccount> = Account::try_from(&compute_account)?;
+            check_authority(
+                &compute_account,
+                ctx.accounts.authority.key(),
+                ctx.remaining_accounts,
+            )?;
+        } else {
+            require_keys_eq!(
+                ctx.accounts.authority.key(),
+                ctx.accounts.snapshot.authority,
+                ErrorDefine::Unauthorized
+            );
+        }
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
 pub struct Create<'info> {
-    #[account(init, payer=user, space = 64)]
+    #[account(init, payer=user, space = ComputeAccount::LEN)]
     pub compute_account: Account<'info, ComputeAccount>,
 
+    #[account(
+        init,
+        payer = user,
+        space = History::LEN,
+        seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()],
+        bump,
+    )]
+    pub history: Account<'info, History>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
@@ -58,9 +759,123 @@ pub struct Create<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// CHECK: still in the legacy layout, parsed by `read_legacy_counter`
+    #[account(mut, owner = crate::ID)]
+    pub compute_account: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = History::LEN,
+        seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()],
+        bump,
+    )]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Close<'info> {
+    #[account(mut, close = destination)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(
+        mut,
+        close = destination,
+        seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()],
+        bump = history.bump,
+    )]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    /// CHECK: only receives the reclaimed rent
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 pub struct ChangeAuthor<'info> {
-    #[account(mut, has_one=authority @ ErrorDefine::Unauthorized)]
+    // authority is checked by `check_authority`, signers for multisig mode come in remaining accounts
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthor<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetBounds<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetClosePolicy<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRateLimit<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetResetWindow<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetHook<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMultisig<'info> {
+    #[account(mut)]
     pub compute_account: Account<'info, ComputeAccount>,
 
     #[account()]
@@ -69,18 +884,236 @@ pub struct ChangeAuthor<'info> {
 
 #[derive(Accounts)]
 pub struct Add<'info> {
-    #[account(mut, has_one=authority @ ErrorDefine::Unauthorized)]
+    #[account(mut)]
     pub compute_account: Account<'info, ComputeAccount>,
 
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
     #[account()]
     pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct Sub<'info> {
-    #[account(mut, has_one=authority @ ErrorDefine::Unauthorized)]
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Batch<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(operator: Pubkey)]
+pub struct GrantOperator<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = Operator::LEN,
+        seeds = [OPERATOR_PDA_SEED, compute_account.key().as_ref(), operator.as_ref()],
+        bump,
+    )]
+    pub operator_account: Account<'info, Operator>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeOperator<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(
+        mut,
+        close = destination,
+        seeds = [OPERATOR_PDA_SEED, compute_account.key().as_ref(), operator_account.operator.as_ref()],
+        bump = operator_account.bump,
+    )]
+    pub operator_account: Account<'info, Operator>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    /// CHECK: only receives the reclaimed rent
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OperatorMutate<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+
+    #[account(
+        mut,
+        seeds = [OPERATOR_PDA_SEED, compute_account.key().as_ref(), operator.key().as_ref()],
+        bump = operator_account.bump,
+    )]
+    pub operator_account: Account<'info, Operator>,
+
+    #[account()]
+    pub operator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct InitSequence<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = Sequence::LEN,
+        seeds = [SEQUENCE_PDA_SEED, owner.key().as_ref(), name.as_bytes()],
+        bump,
+    )]
+    pub sequence: Account<'info, Sequence>,
+
+    // a program PDA works here, it only has to sign `next_id`
+    /// CHECK: only used as seed and stored as owner
+    pub owner: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct NextId<'info> {
+    #[account(
+        mut,
+        has_one = owner @ ErrorDefine::Unauthorized,
+        seeds = [SEQUENCE_PDA_SEED, owner.key().as_ref(), sequence.name.as_bytes()],
+        bump = sequence.bump,
+    )]
+    pub sequence: Account<'info, Sequence>,
+
+    #[account()]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EnableSharding<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u8)]
+pub struct InitShard<'info> {
     pub compute_account: Account<'info, ComputeAccount>,
 
+    #[account(
+        init,
+        payer = user,
+        space = Shard::LEN,
+        seeds = [SHARD_PDA_SEED, compute_account.key().as_ref(), &[index]],
+        bump,
+    )]
+    pub shard: Account<'info, Shard>,
+
     #[account()]
     pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ShardMutate<'info> {
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(
+        mut,
+        seeds = [SHARD_PDA_SEED, compute_account.key().as_ref(), &[shard.index]],
+        bump = shard.bump,
+    )]
+    pub shard: Account<'info, Shard>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Aggregate<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(mut, seeds = [HISTORY_PDA_SEED, compute_account.key().as_ref()], bump = history.bump)]
+    pub history: Account<'info, History>,
+}
+
+#[derive(Accounts)]
+pub struct TakeSnapshot<'info> {
+    #[account(mut)]
+    pub compute_account: Account<'info, ComputeAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = Snapshot::LEN,
+        seeds = [
+            SNAPSHOT_PDA_SEED,
+            compute_account.key().as_ref(),
+            compute_account.snapshot_count.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub snapshot: Account<'info, Snapshot>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSnapshot<'info> {
+    /// CHECK: may already be closed, only parsed by `close_snapshot` while it still exists
+    pub compute_account: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = destination,
+        seeds = [SNAPSHOT_PDA_SEED, compute_account.key().as_ref(), snapshot.index.to_le_bytes().as_ref()],
+        bump = snapshot.bump,
+    )]
+    pub snapshot: Account<'info, Snapshot>,
+
+    #[account()]
+    pub authority: Signer<'info>,
+
+    /// CHECK: only receives the reclaimed rent
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
 }
@@ -0,0 +1,520 @@
+use crate::error::ErrorDefine;
+use crate::state::{
+  ComputeAccount, CounterOp, History, HistoryEntry, Operation, Operator, ResetKind, ResetWindow,
+  Shard, ThresholdCrossed, LEGACY_COMPUTE_ACCOUNT_LEN,
+};
+use crate::{HOOK_PDA_SEED, SHARD_PDA_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::Discriminator;
+
+// single authority mode: `authority` has to be the stored authority
+// multisig mode: at least `threshold` distinct configured signers have to sign,
+// either as `authority` or as one of the remaining accounts
+pub fn check_authority(
+  compute_account: &ComputeAccount,
+  authority: Pubkey,
+  remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+  let multisig = &compute_account.multisig;
+  if !multisig.is_enabled() {
+    require_keys_eq!(authority, compute_account.authority, ErrorDefine::Unauthorized);
+    return Ok(());
+  }
+  let signers = remaining_accounts
+    .iter()
+    .filter(|account| account.is_signer)
+    .map(|account| account.key());
+  let mut approvals: Vec<Pubkey> = Vec::new();
+  for key in std::iter::once(authority).chain(signers) {
+    if multisig.signers.contains(&key) && !approvals.contains(&key) {
+      approvals.push(key);
+    }
+  }
+  require_gte!(approvals.len(), multisig.threshold as usize, ErrorDefine::NotEnoughSigners);
+  Ok(())
+}
+
+// check expiry and count `value` against the operator cap
+pub fn use_operator_quota(operator: &mut Operator, value: i64) -> Result<()> {
+  if let Some(expiry_slot) = operator.expiry_slot {
+    require_gte!(expiry_slot, Clock::get()?.slot, ErrorDefine::OperatorExpired);
+  }
+  let used = operator.used.checked_add(value.unsigned_abs()).ok_or(ErrorDefine::Overflow)?;
+  if let Some(cap) = operator.cap {
+    require_gte!(cap, used, ErrorDefine::OperatorQuotaExceeded);
+  }
+  operator.used = used;
+  Ok(())
+}
+
+// `current` plus the part of `prev` that still falls in the `window` slots ending at
+// `elapsed` slots into the current window
+fn sliding_usage(prev: u64, current: u64, elapsed: u64, window: u64) -> u64 {
+  let overlap = window.saturating_sub(elapsed) as u128;
+  let weighted = prev as u128 * overlap / window as u128;
+  current.saturating_add(weighted as u64)
+}
+
+// count one call changing the total by `value` against the rate limit. Windows are
+// aligned to multiples of `window_slots`, see `RateLimit` for the sliding estimate
+pub fn consume_rate_limit(compute_account: &mut ComputeAccount, value: i64) -> Result<()> {
+  let rate_limit = match compute_account.rate_limit {
+    Some(rate_limit) => rate_limit,
+    None => return Ok(()),
+  };
+  let window = rate_limit.window_slots;
+  let slot = Clock::get()?.slot;
+  let window_start = slot - slot % window;
+  if window_start != compute_account.window_start_slot {
+    // the old window only carries over when it is the one right before
+    let adjacent = compute_account.window_start_slot.saturating_add(window) == window_start;
+    compute_account.prev_window_calls = if adjacent { compute_account.window_calls } else { 0 };
+    compute_account.prev_window_change = if adjacent { compute_account.window_change } else { 0 };
+    compute_account.window_start_slot = window_start;
+    compute_account.window_calls = 0;
+    compute_account.window_change = 0;
+  }
+  let calls = compute_account.window_calls.saturating_add(1);
+  let change = compute_account.window_change.saturating_add(value.unsigned_abs());
+  let elapsed = slot - window_start;
+  if rate_limit.max_calls > 0 {
+    let sliding_calls = sliding_usage(
+      compute_account.prev_window_calls as u64,
+      calls as u64,
+      elapsed,
+      window,
+    );
+    require_gte!(rate_limit.max_calls as u64, sliding_calls, ErrorDefine::RateLimitExceeded);
+  }
+  if rate_limit.max_change > 0 {
+    let sliding_change = sliding_usage(compute_account.prev_window_change, change, elapsed, window);
+    require_gte!(rate_limit.max_change, sliding_change, ErrorDefine::RateLimitExceeded);
+  }
+  compute_account.window_calls = calls;
+  compute_account.window_change = change;
+  Ok(())
+}
+
+// end of the window that contains `now`, for a window that ended at `reset_at`
+pub fn next_reset_at(reset_window: &ResetWindow, reset_at: u64, clock: &Clock) -> u64 {
+  match reset_window.kind {
+    ResetKind::Epoch => clock.epoch.saturating_add(1),
+    ResetKind::Seconds { period } => {
+      let now = clock.unix_timestamp as u64;
+      let elapsed = now.saturating_sub(reset_at) / period + 1;
+      reset_at.saturating_add(elapsed.saturating_mul(period))
+    }
+  }
+}
+
+// move a windowed counter into the current window, keeping the final total of the old one.
+// Runs before `old_total` is read, so a reset is never reported to hooks as a crossing.
+pub fn roll_window(compute_account: &mut ComputeAccount) -> Result<()> {
+  let reset_window = match compute_account.reset_window {
+    Some(reset_window) => reset_window,
+    None => return Ok(()),
+  };
+  let clock = Clock::get()?;
+  let now = match reset_window.kind {
+    ResetKind::Epoch => clock.epoch,
+    ResetKind::Seconds { .. } => clock.unix_timestamp as u64,
+  };
+  if now < compute_account.reset_at {
+    return Ok(());
+  }
+  let reset_at = next_reset_at(&reset_window, compute_account.reset_at, &clock);
+  // start of the window `now` falls in
+  let window_start = match reset_window.kind {
+    ResetKind::Epoch => now,
+    ResetKind::Seconds { period } => reset_at.saturating_sub(period),
+  };
+  // windows that started after `reset_at` saw no mutation and ended on the baseline
+  compute_account.last_window_total = if window_start > compute_account.reset_at {
+    reset_window.baseline
+  } else {
+    compute_account.total
+  };
+  compute_account.total = reset_window.baseline;
+  compute_account.reset_at = reset_at;
+  Ok(())
+}
+
+// callers run `roll_window` first
+pub fn apply_add(compute_account: &mut ComputeAccount, value: i64) -> Result<()> {
+  require_eq!(compute_account.shard_count, 0, ErrorDefine::CounterIsSharded);
+  require!((0..=compute_account.bounds.max_step).contains(&value), ErrorDefine::InvalidStep);
+  let total = compute_account.total.checked_add(value).ok_or(ErrorDefine::Overflow)?;
+  require_gte!(compute_account.bounds.max, total, ErrorDefine::AddError);
+  consume_rate_limit(compute_account, value)?;
+  compute_account.total = total;
+  Ok(())
+}
+
+pub fn apply_sub(compute_account: &mut ComputeAccount, value: i64) -> Result<()> {
+  require_eq!(compute_account.shard_count, 0, ErrorDefine::CounterIsSharded);
+  require!((0..=compute_account.bounds.max_step).contains(&value), ErrorDefine::InvalidStep);
+  let total = compute_account.total.checked_sub(value).ok_or(ErrorDefine::Overflow)?;
+  require_gte!(total, compute_account.bounds.min, ErrorDefine::SubError);
+  consume_rate_limit(compute_account, value)?;
+  compute_account.total = total;
+  Ok(())
+}
+
+// returns the operation and value to record in history
+pub fn apply_op(compute_account: &mut ComputeAccount, op: CounterOp) -> Result<(Operation, i64)> {
+  match op {
+    CounterOp::Add { value } => {
+      apply_add(compute_account, value)?;
+      Ok((Operation::Add, value))
+    }
+    CounterOp::Sub { value } => {
+      apply_sub(compute_account, value)?;
+      Ok((Operation::Sub, value))
+    }
+  }
+}
+
+// split the room left between `total` and the bounds evenly over the shards,
+// so the aggregated total can never leave the bounds
+pub fn reserve_shard(compute_account: &ComputeAccount, shard: &mut Shard) {
+  let bounds = &compute_account.bounds;
+  let above = (bounds.max as i128 - compute_account.total as i128) as u64;
+  let below = (compute_account.total as i128 - bounds.min as i128) as u64;
+  shard.reserved_above = shard_reservation(above, compute_account.shard_count, shard.index);
+  shard.reserved_below = shard_reservation(below, compute_account.shard_count, shard.index);
+}
+
+// the first `headroom % shard_count` shards get one extra unit
+fn shard_reservation(headroom: u64, shard_count: u8, index: u8) -> u64 {
+  let shard_count = shard_count as u64;
+  headroom / shard_count + u64::from((index as u64) < headroom % shard_count)
+}
+
+// shard `index` of `compute_account`, handed in as a writable remaining account
+pub fn load_shard<'info>(
+  compute_account: Pubkey,
+  shard_info: &AccountInfo<'info>,
+  index: u8,
+) -> Result<Account<'info, Shard>> {
+  let shard: Account<Shard> = Account::try_from(shard_info)?;
+  let expected = Pubkey::create_program_address(
+    &[SHARD_PDA_SEED, compute_account.as_ref(), &[index], &[shard.bump]],
+    &crate::ID,
+  )
+  .map_err(|_| ErrorDefine::InvalidShards)?;
+  require_keys_eq!(shard_info.key(), expected, ErrorDefine::InvalidShards);
+  require!(shard_info.is_writable, ErrorDefine::InvalidShards);
+  Ok(shard)
+}
+
+pub fn apply_shard_add(compute_account: &ComputeAccount, shard: &mut Shard, value: i64) -> Result<()> {
+  require!((0..=compute_account.bounds.max_step).contains(&value), ErrorDefine::InvalidStep);
+  let delta = shard.delta.checked_add(value).ok_or(ErrorDefine::Overflow)?;
+  require_gte!(shard.reserved_above as i128, delta as i128, ErrorDefine::ShardReservationExceeded);
+  shard.delta = delta;
+  Ok(())
+}
+
+pub fn apply_shard_sub(compute_account: &ComputeAccount, shard: &mut Shard, value: i64) -> Result<()> {
+  require!((0..=compute_account.bounds.max_step).contains(&value), ErrorDefine::InvalidStep);
+  let delta = shard.delta.checked_sub(value).ok_or(ErrorDefine::Overflow)?;
+  require_gte!(delta as i128, -(shard.reserved_below as i128), ErrorDefine::ShardReservationExceeded);
+  shard.delta = delta;
+  Ok(())
+}
+
+// call `on_counter_threshold(ThresholdCrossed)` on the hook program once for every
+// threshold between `old_total` and the current total. The instruction is signed by the
+// `[HOOK_PDA_SEED, compute_account]` PDA of this program, passed as the first account with
+// the counter as the second one. Hook programs have to check that signer, anyone can call
+// them with made up `ThresholdCrossed` args otherwise. Hook program and signer PDA have to be
+// in the remaining accounts, all other non signer remaining accounts are passed along.
+pub fn fire_hooks<'info>(
+  compute_account: &Account<'info, ComputeAccount>,
+  old_total: i64,
+  remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+  let hook_program = match compute_account.hook_program {
+    Some(hook_program) => hook_program,
+    None => return Ok(()),
+  };
+  let new_total = compute_account.total;
+  let crossed: Vec<i64> = compute_account
+    .thresholds
+    .iter()
+    .copied()
+    .filter(|threshold| (old_total < *threshold) != (new_total < *threshold))
+    .collect();
+  if crossed.is_empty() {
+    return Ok(());
+  }
+  let program_info = remaining_accounts
+    .iter()
+    .find(|account| account.key() == hook_program && account.executable)
+    .ok_or(ErrorDefine::HookProgramMissing)?;
+  let compute_key = compute_account.key();
+  let (hook_signer, bump) =
+    Pubkey::find_program_address(&[HOOK_PDA_SEED, compute_key.as_ref()], &crate::ID);
+  let signer_info = remaining_accounts
+    .iter()
+    .find(|account| account.key() == hook_signer)
+    .ok_or(ErrorDefine::HookSignerMissing)?;
+  let mut hook_accounts = vec![signer_info.clone(), compute_account.to_account_info()];
+  // multisig approvals are never handed to the hook
+  hook_accounts.extend(
+    remaining_accounts
+      .iter()
+      .filter(|account| {
+        !account.is_signer
+          && account.key() != hook_program
+          && account.key() != hook_signer
+          && account.key() != compute_key
+      })
+      .cloned(),
+  );
+  let mut metas = vec![
+    AccountMeta::new_readonly(hook_signer, true),
+    AccountMeta::new_readonly(compute_key, false),
+  ];
+  metas.extend(hook_accounts[2..].iter().map(|account| match account.is_writable {
+    true => AccountMeta::new(account.key(), false),
+    false => AccountMeta::new_readonly(account.key(), false),
+  }));
+  hook_accounts.push(program_info.clone());
+
+  // the hook may read the counter, store the new total first
+  compute_account.exit(&crate::ID)?;
+  let discriminator = &hash(b"global:on_counter_threshold").to_bytes()[..8];
+  let bump_seed = [bump];
+  let signer_seeds: &[&[u8]] = &[HOOK_PDA_SEED, compute_key.as_ref(), &bump_seed];
+  for threshold in crossed {
+    let mut data = discriminator.to_vec();
+    ThresholdCrossed {
+      compute_account: compute_key,
+      threshold,
+      old_total,
+      new_total,
+    }
+    .serialize(&mut data)?;
+    let instruction = Instruction {
+      program_id: hook_program,
+      accounts: metas.clone(),
+      data,
+    };
+    invoke_signed(&instruction, &hook_accounts, &[signer_seeds])?;
+  }
+  Ok(())
+}
+
+fn history_entry(operation: Operation, value: i64, total: i64, signer: Pubkey) -> Result<HistoryEntry> {
+  let clock = Clock::get()?;
+  Ok(HistoryEntry {
+    operation: operation.to_code(),
+    value,
+    total,
+    signer,
+    slot: clock.slot,
+    timestamp: clock.unix_timestamp,
+    new_authority: Pubkey::default(),
+  })
+}
+
+pub fn record_history(
+  history: &mut History,
+  operation: Operation,
+  value: i64,
+  total: i64,
+  signer: Pubkey,
+) -> Result<()> {
+  history.record(history_entry(operation, value, total, signer)?);
+  Ok(())
+}
+
+// proposal and acceptance both keep the key authority goes to
+pub fn record_authority_change(
+  history: &mut History,
+  operation: Operation,
+  total: i64,
+  signer: Pubkey,
+  new_authority: Pubkey,
+) -> Result<()> {
+  let mut entry = history_entry(operation, 0, total, signer)?;
+  entry.new_authority = new_authority;
+  history.record(entry);
+  Ok(())
+}
+
+// read `total` and `authority` from an account still in the first layout:
+// discriminator, i8 total, authority
+pub fn read_legacy_counter(data: &[u8]) -> Result<(i64, Pubkey)> {
+  require_eq!(data.len(), LEGACY_COMPUTE_ACCOUNT_LEN, ErrorDefine::NotLegacyAccount);
+  require!(data[..8] == ComputeAccount::discriminator(), ErrorDefine::NotLegacyAccount);
+  let total = data[8] as i8 as i64;
+  let authority = Pubkey::try_from(&data[9..41]).map_err(|_| ErrorDefine::NotLegacyAccount)?;
+  Ok((total, authority))
+}
+
+pub fn transfer_sol<'info>(
+  sender: AccountInfo<'info>,
+  receiver: AccountInfo<'info>,
+  amount: u64,
+  system_program: AccountInfo<'info>,
+) -> Result<()> {
+  let transfer_sol_instruction = anchor_lang::system_program::Transfer {
+    from: sender.to_account_info(),
+    to: receiver.to_account_info(),
+  };
+  let cpi_ctx_sol = CpiContext::new(system_program.to_account_info(), transfer_sol_instruction);
+  anchor_lang::system_program::transfer(cpi_ctx_sol, amount)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::state::{Bounds, Multisig, MAX_SHARDS};
+
+  fn counter(total: i64, min: i64, max: i64, shard_count: u8) -> ComputeAccount {
+    ComputeAccount {
+      total,
+      authority: Pubkey::new_unique(),
+      bounds: Bounds {
+        min,
+        max,
+        max_step: 10,
+      },
+      pending_authority: None,
+      pending_expiry_slot: None,
+      multisig: Multisig::default(),
+      shard_count,
+      rate_limit: None,
+      window_start_slot: 0,
+      window_calls: 0,
+      window_change: 0,
+      prev_window_calls: 0,
+      prev_window_change: 0,
+      snapshot_count: 0,
+      hook_program: None,
+      thresholds: Vec::new(),
+      reset_window: None,
+      reset_at: 0,
+      last_window_total: 0,
+      operator_count: 0,
+      close_only_if_zero: false,
+    }
+  }
+
+  fn shard(index: u8) -> Shard {
+    Shard {
+      compute_account: Pubkey::new_unique(),
+      index,
+      delta: 0,
+      reserved_above: 0,
+      reserved_below: 0,
+      bump: 0,
+    }
+  }
+
+  #[test]
+  fn shard_reservation_hands_the_leftover_to_the_first_shards() {
+    let reservations: Vec<u64> = (0..4).map(|index| shard_reservation(10, 4, index)).collect();
+    assert_eq!(reservations, vec![3, 3, 2, 2]);
+    let reservations: Vec<u64> = (0..3).map(|index| shard_reservation(2, 3, index)).collect();
+    assert_eq!(reservations, vec![1, 1, 0]);
+    assert_eq!(shard_reservation(0, 4, 0), 0);
+  }
+
+  #[test]
+  fn shard_reservations_add_up_to_the_headroom() {
+    for headroom in [0, 1, 15, 16, 17, u64::MAX] {
+      for shard_count in 1..=MAX_SHARDS {
+        let sum: u128 = (0..shard_count)
+          .map(|index| shard_reservation(headroom, shard_count, index) as u128)
+          .sum();
+        assert_eq!(sum, headroom as u128);
+      }
+    }
+  }
+
+  #[test]
+  fn reserve_shard_splits_the_room_on_both_sides_of_total() {
+    let compute_account = counter(2, -5, 10, 4);
+    let shards: Vec<Shard> = (0..4)
+      .map(|index| {
+        let mut shard = shard(index);
+        reserve_shard(&compute_account, &mut shard);
+        shard
+      })
+      .collect();
+    let above: Vec<u64> = shards.iter().map(|shard| shard.reserved_above).collect();
+    let below: Vec<u64> = shards.iter().map(|shard| shard.reserved_below).collect();
+    assert_eq!(above, vec![2, 2, 2, 2]);
+    assert_eq!(below, vec![2, 2, 2, 1]);
+  }
+
+  #[test]
+  fn reserve_shard_handles_the_full_i64_range() {
+    let compute_account = counter(i64::MIN, i64::MIN, i64::MAX, 1);
+    let mut shard = shard(0);
+    reserve_shard(&compute_account, &mut shard);
+    assert_eq!(shard.reserved_above, u64::MAX);
+    assert_eq!(shard.reserved_below, 0);
+  }
+
+  #[test]
+  fn shard_writes_stay_within_the_reservation() {
+    let compute_account = counter(0, -1, 2, 1);
+    let mut shard = shard(0);
+    reserve_shard(&compute_account, &mut shard);
+    assert!(apply_shard_add(&compute_account, &mut shard, 2).is_ok());
+    assert!(apply_shard_add(&compute_account, &mut shard, 1).is_err());
+    assert!(apply_shard_sub(&compute_account, &mut shard, 3).is_ok());
+    assert!(apply_shard_sub(&compute_account, &mut shard, 1).is_err());
+    assert_eq!(shard.delta, -1);
+  }
+
+  fn legacy_data(total: i8, authority: Pubkey) -> Vec<u8> {
+    let mut data = vec![0; LEGACY_COMPUTE_ACCOUNT_LEN];
+    data[..8].copy_from_slice(&ComputeAccount::discriminator());
+    data[8] = total as u8;
+    data[9..41].copy_from_slice(authority.as_ref());
+    data
+  }
+
+  #[test]
+  fn read_legacy_counter_sign_extends_the_total() {
+    let authority = Pubkey::new_unique();
+    assert_eq!(read_legacy_counter(&legacy_data(-5, authority)).unwrap(), (-5, authority));
+    assert_eq!(read_legacy_counter(&legacy_data(10, authority)).unwrap(), (10, authority));
+    assert_eq!(read_legacy_counter(&legacy_data(i8::MIN, authority)).unwrap(), (-128, authority));
+  }
+
+  #[test]
+  fn read_legacy_counter_rejects_other_layouts() {
+    let data = legacy_data(1, Pubkey::new_unique());
+    assert!(read_legacy_counter(&data[..LEGACY_COMPUTE_ACCOUNT_LEN - 1]).is_err());
+    let mut migrated = data.clone();
+    migrated.resize(ComputeAccount::LEN, 0);
+    assert!(read_legacy_counter(&migrated).is_err());
+    let mut other_account = data;
+    other_account[..8].copy_from_slice(&History::discriminator());
+    assert!(read_legacy_counter(&other_account).is_err());
+  }
+
+  #[test]
+  fn sliding_usage_weights_the_previous_window_by_its_overlap() {
+    assert_eq!(sliding_usage(10, 0, 0, 100), 10);
+    assert_eq!(sliding_usage(10, 2, 50, 100), 7);
+    assert_eq!(sliding_usage(10, 2, 90, 100), 3);
+    assert_eq!(sliding_usage(10, 2, 100, 100), 2);
+  }
+
+  #[test]
+  fn sliding_usage_does_not_overflow() {
+    assert_eq!(sliding_usage(u64::MAX, u64::MAX, 0, 1), u64::MAX);
+    assert_eq!(sliding_usage(u64::MAX, 0, 1, u64::MAX), u64::MAX - 1);
+  }
+}
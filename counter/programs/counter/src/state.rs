@@ -0,0 +1,397 @@
+use anchor_lang::prelude::*;
+
+// range of `total` and the largest value a single add/sub may apply
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Bounds {
+  pub min: i64,
+  pub max: i64,
+  pub max_step: i64,
+}
+
+impl Bounds {
+  pub const LEN: usize = 3 * 8; // i64
+
+  // limits that used to be hardcoded in `add` / `sub`
+  pub const LEGACY: Bounds = Bounds {
+    min: -5,
+    max: 10,
+    max_step: 15,
+  };
+
+  pub fn is_valid(&self) -> bool {
+    self.min <= self.max && self.max_step > 0
+  }
+
+  pub fn contains(&self, total: i64) -> bool {
+    self.min <= total && total <= self.max
+  }
+}
+
+// caps on add/sub over any `window_slots` slots. Usage is kept per aligned window and the
+// sliding usage is the current window plus the previous one weighted by how much of it
+// still overlaps the last `window_slots` slots
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RateLimit {
+  pub window_slots: u64,
+  // 0 disables the cap on number of calls
+  pub max_calls: u32,
+  // 0 disables the cap on summed absolute change
+  pub max_change: u64,
+}
+
+impl RateLimit {
+  pub const LEN: usize = 8 // u64
+    + 4 // u32
+    + 8; // u64
+}
+
+// what moves a windowed counter into its next window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum ResetKind {
+  Epoch,
+  Seconds { period: u64 },
+}
+
+// `total` goes back to `baseline` on the first mutation of every new window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ResetWindow {
+  pub kind: ResetKind,
+  pub baseline: i64,
+}
+
+impl ResetWindow {
+  pub const LEN: usize = 1 + 8 // ResetKind
+    + 8; // i64
+}
+
+pub const MAX_THRESHOLDS: usize = 4;
+
+// instruction args sent to the hook program when `total` crosses a threshold,
+// only trustworthy when signed by the hook PDA of the counter, see `fire_hooks`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ThresholdCrossed {
+  pub compute_account: Pubkey,
+  pub threshold: i64,
+  pub old_total: i64,
+  pub new_total: i64,
+}
+
+pub const MAX_MULTISIG_SIGNERS: usize = 5;
+
+// `threshold == 0` keeps the counter in single authority mode
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct Multisig {
+  pub signers: Vec<Pubkey>,
+  pub threshold: u8,
+}
+
+impl Multisig {
+  pub const LEN: usize = 4 + MAX_MULTISIG_SIGNERS * 32 // Vec<PubKey>
+    + 1; // u8
+
+  pub fn is_enabled(&self) -> bool {
+    self.threshold > 0
+  }
+}
+
+// accounts created with the first layout (`total: i8`) were allocated with this size
+pub const LEGACY_COMPUTE_ACCOUNT_LEN: usize = 64;
+
+#[account]
+pub struct ComputeAccount {
+  pub total: i64,
+  pub authority: Pubkey,
+  pub bounds: Bounds,
+  // proposed by `change_authority`, takes over once it signs `accept_authority`
+  pub pending_authority: Option<Pubkey>,
+  // last slot the proposal can be accepted in, `None` never expires
+  pub pending_expiry_slot: Option<u64>,
+  pub multisig: Multisig,
+  // number of `Shard` accounts `add` / `sub` go through, 0 when not sharded
+  pub shard_count: u8,
+  pub rate_limit: Option<RateLimit>,
+  // usage of the current rate limit window
+  pub window_start_slot: u64,
+  pub window_calls: u32,
+  pub window_change: u64,
+  // usage of the window before it, weighted into the sliding window
+  pub prev_window_calls: u32,
+  pub prev_window_change: u64,
+  // index the next `Snapshot` is created with
+  pub snapshot_count: u64,
+  // program called by `fire_hooks` when an add/sub crosses one of `thresholds`
+  pub hook_program: Option<Pubkey>,
+  pub thresholds: Vec<i64>,
+  pub reset_window: Option<ResetWindow>,
+  // epoch or unix timestamp (per `ResetKind`) the current window ends at
+  pub reset_at: u64,
+  // final total of the previous window
+  pub last_window_total: i64,
+  // live `Operator` grants, `close` needs them revoked first
+  pub operator_count: u32,
+  // `close` is refused while the total (shards included) is not 0
+  pub close_only_if_zero: bool,
+}
+
+impl ComputeAccount {
+  pub const LEN: usize = 8 // internal discriminator
+    + 8 // i64
+    + 32 // PubKey
+    + Bounds::LEN
+    + 1 + 32 // Option<PubKey>
+    + 1 + 8 // Option<u64>
+    + Multisig::LEN
+    + 1 // u8
+    + 1 + RateLimit::LEN // Option<RateLimit>
+    + 8 // u64
+    + 4 // u32
+    + 8 // u64
+    + 4 // u32
+    + 2 * 8 // u64
+    + 1 + 32 // Option<PubKey>
+    + 4 + MAX_THRESHOLDS * 8 // Vec<i64>
+    + 1 + ResetWindow::LEN // Option<ResetWindow>
+    + 8 // u64
+    + 8 // i64
+    + 4 // u32
+    + 1; // bool
+}
+
+pub const HISTORY_CAPACITY: usize = 32;
+pub const MAX_BATCH_OPS: usize = 16;
+
+// single step of a `batch` call
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum CounterOp {
+  Add { value: i64 },
+  Sub { value: i64 },
+}
+
+// define operation recorded in history (current support: Add, Sub, ChangeAuthority, Aggregate & AcceptAuthority)
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operation {
+  Add,
+  Sub,
+  ChangeAuthority,
+  // shard deltas folded into the total, `value` is their sum
+  Aggregate,
+  AcceptAuthority,
+}
+
+impl Operation {
+  pub fn to_code(&self) -> u8 {
+    match self {
+      Operation::Add => 1,
+      Operation::Sub => 2,
+      Operation::ChangeAuthority => 3,
+      Operation::Aggregate => 4,
+      Operation::AcceptAuthority => 5,
+    }
+  }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct HistoryEntry {
+  pub operation: u8,
+  pub value: i64,
+  // total after the operation
+  pub total: i64,
+  pub signer: Pubkey,
+  pub slot: u64,
+  pub timestamp: i64,
+  // proposed / accepted authority of an authority change, default key otherwise
+  pub new_authority: Pubkey,
+}
+
+impl HistoryEntry {
+  pub const LEN: usize = 1 // u8
+    + 2 * 8 // i64
+    + 32 // PubKey
+    + 8 // u64
+    + 8 // i64
+    + 32; // PubKey
+}
+
+// ring buffer of the last `HISTORY_CAPACITY` operations on a counter
+#[account]
+pub struct History {
+  pub compute_account: Pubkey,
+  // slot the next entry goes to once the buffer is full
+  pub head: u32,
+  pub entries: Vec<HistoryEntry>,
+  pub bump: u8,
+}
+
+impl History {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 4 // u32
+    + 4 + HISTORY_CAPACITY * HistoryEntry::LEN // Vec<HistoryEntry>
+    + 1; // u8
+
+  pub fn record(&mut self, entry: HistoryEntry) {
+    if self.entries.len() < HISTORY_CAPACITY {
+      self.entries.push(entry);
+    } else {
+      // overwrite the oldest entry
+      self.entries[self.head as usize] = entry;
+    }
+    self.head = (self.head + 1) % HISTORY_CAPACITY as u32;
+  }
+}
+
+// key allowed to add/sub on a counter without holding its authority
+#[account]
+pub struct Operator {
+  pub compute_account: Pubkey,
+  pub operator: Pubkey,
+  // cap on the cumulative change applied by this operator, `None` is unlimited
+  pub cap: Option<u64>,
+  pub used: u64,
+  // last slot the operator can act in, `None` never expires
+  pub expiry_slot: Option<u64>,
+  pub bump: u8,
+}
+
+impl Operator {
+  pub const LEN: usize = 8 // internal discriminator
+    + 2 * 32 // PubKey
+    + 2 * (1 + 8) // Option<u64>
+    + 8 // u64
+    + 1; // u8
+}
+
+pub const MAX_SEQUENCE_NAME_LEN: usize = 32;
+
+// id generator derived from (owner, name), bumped by `next_id`
+#[account]
+pub struct Sequence {
+  pub owner: Pubkey,
+  pub name: String,
+  // last id handed out, the first call to `next_id` returns 1
+  pub value: u64,
+  pub bump: u8,
+}
+
+impl Sequence {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 4 + MAX_SEQUENCE_NAME_LEN // String
+    + 8 // u64
+    + 1; // u8
+}
+
+pub const MAX_SHARDS: u8 = 16;
+
+// write target of a sharded counter, folded back into the parent by `aggregate`
+#[account]
+pub struct Shard {
+  pub compute_account: Pubkey,
+  pub index: u8,
+  // change applied through this shard since the last aggregation
+  pub delta: i64,
+  // share of the parent headroom, `delta` stays within [-reserved_below, reserved_above]
+  pub reserved_above: u64,
+  pub reserved_below: u64,
+  pub bump: u8,
+}
+
+impl Shard {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 1 // u8
+    + 8 // i64
+    + 2 * 8 // u64
+    + 1; // u8
+}
+
+// immutable copy of a counter at `slot`, for sharded counters `total` is as of the last aggregate
+#[account]
+pub struct Snapshot {
+  pub compute_account: Pubkey,
+  pub index: u64,
+  pub total: i64,
+  // authority at `slot`, can still close the snapshot after the counter is closed
+  pub authority: Pubkey,
+  pub slot: u64,
+  pub bump: u8,
+}
+
+impl Snapshot {
+  pub const LEN: usize = 8 // internal discriminator
+    + 2 * 32 // PubKey
+    + 2 * 8 // u64
+    + 8 // i64
+    + 1; // u8
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(value: i64) -> HistoryEntry {
+    HistoryEntry {
+      operation: Operation::Add.to_code(),
+      value,
+      ..HistoryEntry::default()
+    }
+  }
+
+  fn history() -> History {
+    History {
+      compute_account: Pubkey::new_unique(),
+      head: 0,
+      entries: Vec::new(),
+      bump: 0,
+    }
+  }
+
+  #[test]
+  fn history_appends_until_full() {
+    let mut history = history();
+    for value in 0..HISTORY_CAPACITY as i64 {
+      history.record(entry(value));
+    }
+    assert_eq!(history.entries.len(), HISTORY_CAPACITY);
+    assert_eq!(history.head, 0);
+    let values: Vec<i64> = history.entries.iter().map(|entry| entry.value).collect();
+    assert_eq!(values, (0..HISTORY_CAPACITY as i64).collect::<Vec<i64>>());
+  }
+
+  #[test]
+  fn full_history_overwrites_the_oldest_entry() {
+    let mut history = history();
+    for value in 0..HISTORY_CAPACITY as i64 + 3 {
+      history.record(entry(value));
+    }
+    assert_eq!(history.entries.len(), HISTORY_CAPACITY);
+    assert_eq!(history.head, 3);
+    assert_eq!(history.entries[0].value, HISTORY_CAPACITY as i64);
+    assert_eq!(history.entries[2].value, HISTORY_CAPACITY as i64 + 2);
+    // `head` points at the oldest entry left
+    assert_eq!(history.entries[3].value, 3);
+  }
+
+  #[test]
+  fn history_wraps_more_than_once() {
+    let mut history = history();
+    for value in 0..2 * HISTORY_CAPACITY as i64 + 1 {
+      history.record(entry(value));
+    }
+    assert_eq!(history.entries.len(), HISTORY_CAPACITY);
+    assert_eq!(history.head, 1);
+    assert_eq!(history.entries[0].value, 2 * HISTORY_CAPACITY as i64);
+    assert_eq!(history.entries[1].value, HISTORY_CAPACITY as i64 + 1);
+  }
+
+  #[test]
+  fn history_fits_its_account_space() {
+    let mut history = history();
+    for value in 0..HISTORY_CAPACITY as i64 {
+      history.record(entry(value));
+    }
+    let mut data = Vec::new();
+    history.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), History::LEN);
+  }
+}
@@ -3,12 +3,11 @@ pub mod state;
 pub mod processor;
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
-use anchor_spl::token::{Mint, Token, Transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::error::ErrorCode;
 use crate::state::Stage;
-use crate::processor::{transfer_token, to_close_account};
+use crate::processor::{transfer_token, transfer_fee, to_close_account};
 declare_id!("C3iRXuEMdHwVUXoPtsMBKps5eVS9KLh7o57gpsgQuNCj");
 
 #[program]
@@ -30,13 +29,14 @@ pub mod escrow {
     pub fn deposit(ctx: Context<DepositInstruction>, amount: u64) -> Result<()> {
         let state = &mut ctx.accounts.state_account;
         let bump = state.bumps.state_bump;
-        // handle transfer token
-        state.amount += amount;
+        // handle transfer token, the escrow only receives what is left after a Token-2022 transfer fee
+        state.amount += amount - transfer_fee(&ctx.accounts.mint, amount)?;
         let seeds = &[&[b"state", bytemuck::bytes_of(&bump)][..]];
         transfer_token(
             ctx.accounts.user_associated_account.to_account_info(), 
             ctx.accounts.escrow_wallet_associate_account.to_account_info(), 
             ctx.accounts.user.to_account_info(),
+            &ctx.accounts.mint,
             amount,
             seeds,
             ctx.accounts.token_program.to_account_info()
@@ -62,6 +62,7 @@ pub mod escrow {
             ctx.accounts.escrow_wallet_associate_account.to_account_info(), 
             ctx.accounts.user_associated_account.to_account_info(),
             state.to_account_info(),
+            &ctx.accounts.mint,
             amount,
             seeds,
             ctx.accounts.token_program.to_account_info()
@@ -123,11 +124,12 @@ pub struct InitState<'info> {
         bump,
         token::mint=mint,
         token::authority=state_account,
+        token::token_program=token_program,
     )]
-    escrow_wallet_associate_account: Account<'info, TokenAccount>,
-    mint: Account<'info, Mint>,
+    escrow_wallet_associate_account: InterfaceAccount<'info, TokenAccount>,
+    mint: InterfaceAccount<'info, Mint>,
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
     rent: Sysvar<'info, Rent>,
 }
 
@@ -145,19 +147,19 @@ pub struct DepositInstruction<'info> {
         token::mint=mint,
         token::authority=state_account
     )]
-    escrow_wallet_associate_account: Account<'info, TokenAccount>,
+    escrow_wallet_associate_account: InterfaceAccount<'info, TokenAccount>,
     
-    mint: Account<'info, Mint>,
+    mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint=mint,
         token::authority=user
     )]
-    user_associated_account: Account<'info, TokenAccount>,
+    user_associated_account: InterfaceAccount<'info, TokenAccount>,
 
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -173,15 +175,15 @@ pub struct WithDrawInstruction<'info> {
         token::mint=mint,
         token::authority=state_account
     )]
-    escrow_wallet_associate_account: Account<'info, TokenAccount>,
-    mint: Account<'info, Mint>,
+    escrow_wallet_associate_account: InterfaceAccount<'info, TokenAccount>,
+    mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         token::mint = mint,
         token::authority = user
     )]
-    user_associated_account: Account<'info, TokenAccount>,
+    user_associated_account: InterfaceAccount<'info, TokenAccount>,
     // system
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
 }
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{CloseAccount, Transfer};
+use anchor_spl::token_interface::{CloseAccount, Mint, TransferChecked};
+
+pub use token_fee::transfer_fee;
 
 pub fn transfer_sol<'info>(
   sender: AccountInfo<'info>,
@@ -23,16 +25,19 @@ pub fn transfer_sol<'info>(
   return Ok(());
 }
 
+// works with both the Token and the Token-2022 program
 pub fn transfer_token<'info>(
   sender: AccountInfo<'info>,
   receiver: AccountInfo<'info>,
   user: AccountInfo<'info>,
+  mint: &InterfaceAccount<'info, Mint>,
   amount: u64,
   seeds: &[&[&[u8]]],
   token_program: AccountInfo<'info>,
 ) -> Result<()> {
-  let transfer_instruction_account = Transfer {
+  let transfer_instruction_account = TransferChecked {
     from: sender.to_account_info(),
+    mint: mint.to_account_info(),
     to: receiver.to_account_info(),
     authority: user.to_account_info(),
   };
@@ -42,7 +47,7 @@ pub fn transfer_token<'info>(
     seeds,
   );
 
-  anchor_spl::token::transfer(cpi_ctx, amount)?;
+  anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
   Ok(())
 }
 
@@ -63,6 +68,6 @@ pub fn to_close_account<'info>(
     close_account,
     outer.as_slice(),
   );
-  anchor_spl::token::close_account(cpi_ctx)?;
+  anchor_spl::token_interface::close_account(cpi_ctx)?;
   Ok(())
 }
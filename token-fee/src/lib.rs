@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use anchor_spl::token_interface::Mint;
+
+// part of `amount` withheld by the transfer fee extension of a Token-2022 mint, 0 for other mints
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+  let mint_info = mint.to_account_info();
+  if *mint_info.owner != spl_token_2022::ID {
+    return Ok(0);
+  }
+  let data = mint_info.try_borrow_data()?;
+  let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+  let fee = match mint_state.get_extension::<TransferFeeConfig>() {
+    Ok(fee_config) => fee_config
+      .calculate_epoch_fee(Clock::get()?.epoch, amount)
+      .ok_or(ProgramError::InvalidArgument)?,
+    Err(_) => 0,
+  };
+  Ok(fee)
+}
@@ -250,6 +250,7 @@ import { token } from "@project-serum/anchor/dist/cjs/utils";
 
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
       },
       signers: [buyer],
     }
@@ -3,12 +3,11 @@ pub mod state;
 pub mod processor;
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
-use anchor_spl::token::{Mint, Token};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::error::ErrorCode;
 use crate::state::{Stage, StateAccount};
-use crate::processor::{transfer_sol, transfer_token};
+use crate::processor::{transfer_sol, transfer_token, transfer_fee};
 
 declare_id!("4kVr2h7SZkWVV7DBoYEkL4gV7bzcp2T9TbuFqmNBcnUc");
 
@@ -42,11 +41,13 @@ pub mod trade_nft {
         let state = &mut ctx.accounts.state_account;
         state.price_sol = price_sol;
         state.price_token = price_token;
-        state.amount = amount;
+        // the escrow only receives what is left after a Token-2022 transfer fee
+        state.amount = amount - transfer_fee(&ctx.accounts.mint_nft, amount)?;
         transfer_token(
             ctx.accounts.seller_associate_nft_account.to_account_info(),
             ctx.accounts.escrow_associate_nft_wallet.to_account_info(),
             ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.mint_nft,
             amount,
             ctx.accounts.token_program.to_account_info(),
             None
@@ -82,8 +83,9 @@ pub mod trade_nft {
                 ctx.accounts.buyer_associate_token_account.to_account_info(),
                 ctx.accounts.seller_associate_token_account.to_account_info(),
                 ctx.accounts.buyer.to_account_info(),
+                &ctx.accounts.mint_token,
                 total_token_price,
-                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.payment_token_program.to_account_info(),
                 None
             )?;
         }
@@ -92,6 +94,7 @@ pub mod trade_nft {
             ctx.accounts.escrow_associate_nft_wallet.to_account_info(),
             ctx.accounts.buyer_associate_nft_account.to_account_info(),
             state_account.to_account_info(),
+            &ctx.accounts.mint_nft,
             amount,
             ctx.accounts.token_program.to_account_info(),
             Some(seeds)
@@ -116,6 +119,7 @@ pub mod trade_nft {
             ctx.accounts.escrow_associate_nft_wallet.to_account_info(), 
             ctx.accounts.seller_associate_nft_account.to_account_info(), 
             state.to_account_info(),
+            &ctx.accounts.mint_nft,
             amount,
             ctx.accounts.token_program.to_account_info(),
             Some(seeds)
@@ -146,12 +150,13 @@ pub struct Create<'info> {
         bump,
         token::mint=mint_nft,
         token::authority=state_account,
+        token::token_program=token_program,
     )]
-    escrow_associate_nft_wallet: Account<'info, TokenAccount>,
+    escrow_associate_nft_wallet: InterfaceAccount<'info, TokenAccount>,
     // mint nft sell
     #[account(constraint = mint_nft.decimals == 0 @ ErrorCode::InvalidNFT)]
-    mint_nft: Account<'info, Mint>,
-    mint_token: Account<'info, Mint>,
+    mint_nft: InterfaceAccount<'info, Mint>,
+    mint_token: InterfaceAccount<'info, Mint>,
     // seller associate nft account
     #[account(
         mut,
@@ -159,11 +164,11 @@ pub struct Create<'info> {
         token::authority=seller,
         constraint = seller_associate_nft_account.amount > 0 @ ErrorCode::InsufficientFunds
     )]
-    seller_associate_nft_account: Account<'info, TokenAccount>,
+    seller_associate_nft_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut, constraint = seller.lamports() > 0 && seller.data_is_empty())]
     seller: Signer<'info>,
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
     rent: Sysvar<'info, Rent>,
 }
 #[derive(Accounts)]
@@ -181,22 +186,22 @@ pub struct SellInstruction<'info> {
         seeds = [ESCROW_NFT_ASSOCIATE_PDA_SEED, seller.key().as_ref(), mint_nft.key().as_ref()],
         bump = state_account.bumps.wallet_nft_bump
     )]
-    escrow_associate_nft_wallet: Account<'info, TokenAccount>,
+    escrow_associate_nft_wallet: InterfaceAccount<'info, TokenAccount>,
     // mint nft sell
     #[account(constraint = mint_nft.decimals == 0 @ ErrorCode::InvalidNFT)]
-    mint_nft: Account<'info, Mint>,
-    mint_token: Account<'info, Mint>,
+    mint_nft: InterfaceAccount<'info, Mint>,
+    mint_token: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         token::mint=mint_nft,
         token::authority=seller,
         constraint = seller_associate_nft_account.amount > 0 @ ErrorCode::InsufficientFunds
     )]
-    seller_associate_nft_account: Account<'info, TokenAccount>,
+    seller_associate_nft_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut, constraint = seller.lamports() > 0 && seller.data_is_empty())]
     seller: Signer<'info>,
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
 }
 
 
@@ -211,7 +216,7 @@ pub struct EditPriceInstruction<'info> {
     seller: Signer<'info>,
     // system
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
 }
 #[derive(Accounts)]
 #[instruction(amount: u64, price_sol: u64, price_token: u64)]
@@ -230,34 +235,43 @@ pub struct BuyInstruction<'info> {
         seeds = [ESCROW_NFT_ASSOCIATE_PDA_SEED, seller.key().as_ref(), mint_nft.key().as_ref()],
         bump = state_account.bumps.wallet_nft_bump,
         token::mint = mint_nft,
-        token::authority = state_account
+        token::authority = state_account,
+        token::token_program = token_program
     )]
-    escrow_associate_nft_wallet: Box<Account<'info, TokenAccount>>,
-    mint_nft: Account<'info, Mint>,
-    mint_token: Account<'info, Mint>,
+    escrow_associate_nft_wallet: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = token_program)]
+    mint_nft: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = payment_token_program)]
+    mint_token: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         token::mint = mint_nft,
-        token::authority = buyer
+        token::authority = buyer,
+        token::token_program = token_program
     )]
-    buyer_associate_nft_account: Box<Account<'info, TokenAccount>>,
+    buyer_associate_nft_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         token::mint = mint_token,
-        token::authority = buyer
+        token::authority = buyer,
+        token::token_program = payment_token_program
     )]
-    buyer_associate_token_account: Box<Account<'info, TokenAccount>>,
+    buyer_associate_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         token::mint = mint_token,
-        token::authority = seller
+        token::authority = seller,
+        token::token_program = payment_token_program
     )]
-    seller_associate_token_account: Box<Account<'info, TokenAccount>>,
+    seller_associate_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut, constraint = buyer.lamports() > amount * price_sol @ ErrorCode::InsufficientFunds)]
     buyer: Signer<'info>,
     // // system
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    // program of the NFT mint
+    token_program: Interface<'info, TokenInterface>,
+    // program of the payment mint, the NFT and the payment may be on different token programs
+    payment_token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -276,18 +290,18 @@ pub struct CancelSellInstruction<'info> {
         token::mint = mint_nft,
         token::authority = state_account
     )]
-    escrow_associate_nft_wallet: Account<'info, TokenAccount>,
+    escrow_associate_nft_wallet: InterfaceAccount<'info, TokenAccount>,
     #[account(constraint = mint_nft.decimals == 0 @ ErrorCode::InvalidNFT)]
-    mint_nft: Account<'info, Mint>,
+    mint_nft: InterfaceAccount<'info, Mint>,
     // refund wallet
     #[account(
         mut,
         token::mint = mint_nft,
         token::authority = seller
     )]
-    seller_associate_nft_account: Account<'info, TokenAccount>,
+    seller_associate_nft_account: InterfaceAccount<'info, TokenAccount>,
     // system
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
 }
 
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::Transfer;
+use anchor_spl::token_interface::{Mint, TransferChecked};
+
+pub use token_fee::transfer_fee;
 
 pub fn transfer_sol<'info>(
   sender: AccountInfo<'info>,
@@ -29,17 +31,19 @@ pub fn transfer_sol<'info>(
   return Ok(());
 }
 
-// transfer fungible token & nft token
+// transfer fungible token & nft token, through either the Token or the Token-2022 program
 pub fn transfer_token<'info>(
   sender: AccountInfo<'info>,
   receiver: AccountInfo<'info>,
   user: AccountInfo<'info>,
+  mint: &InterfaceAccount<'info, Mint>,
   amount: u64,
   token_program: AccountInfo<'info>,
   seeds: Option<&[&[&[u8]]]>,
 ) -> Result<()> {
-  let transfer_instruction_account = Transfer {
+  let transfer_instruction_account = TransferChecked {
     from: sender.to_account_info(),
+    mint: mint.to_account_info(),
     to: receiver.to_account_info(),
     authority: user.to_account_info(),
   };
@@ -59,7 +63,7 @@ pub fn transfer_token<'info>(
       );
     }
   }
-  anchor_spl::token::transfer(cpi_ctx, amount)?;
+  anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
   Ok(())
 }
 
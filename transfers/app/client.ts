@@ -40,14 +40,23 @@ import {
 
   const mint2 = new PublicKey("3xmazVDSPt1oaiboWo9QYjpwvKs8LjpdjFaCcHh6Chco");
 
-  const configAccount = anchor.web3.Keypair.generate();
-  const sig = await program.rpc.init(mint2, {
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from("config")],
+    Program_ID
+  );
+  // only the upgrade authority of the program can create the config
+  const [programData] = await PublicKey.findProgramAddress(
+    [Program_ID.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  const sig = await program.rpc.init([mint2], {
     accounts: {
-      user: provider.wallet.publicKey,
+      admin: provider.wallet.publicKey,
+      program: Program_ID,
+      programData: programData,
       systemProgram: SystemProgram.programId,
-      configAccount: configAccount.publicKey,
+      configAccount: configAccount,
     },
-    signers: [configAccount],
   });
   console.log("init: ", sig);
 
@@ -61,29 +70,41 @@ import {
     toWallet.publicKey
   );
 
-  const mint_tx = new anchor.web3.Transaction().add(
-    // Create the ATA account that is associated with our To wallet
-    createAssociatedTokenAccountInstruction(
-      provider.wallet.publicKey,
-      toATA,
-      toWallet.publicKey,
-      mintAddress
-    )
-  );
-  await provider.sendAndConfirm(mint_tx, []);
-
+  // toATA does not exist yet, `transfer` creates it and the sender pays for it
 
-  const tx1 = await program.rpc.transfer(new BN(10000000), {
+  // invoice id, a second transfer with the same reference is rejected
+  const reference = Keypair.generate().publicKey.toBuffer();
+  const [receiptAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from("receipt"), provider.wallet.publicKey.toBuffer(), reference],
+    Program_ID
+  );
+  // checked on every transfer, only enforced once the sender called `set_policy`
+  const [policyAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from("policy"), provider.wallet.publicKey.toBuffer(), mintAddress.toBuffer()],
+    Program_ID
+  );
+  // only read while the config is in allowlist or blocklist mode
+  const [recipientEntry] = await PublicKey.findProgramAddress(
+    [Buffer.from("recipient"), toWallet.publicKey.toBuffer()],
+    Program_ID
+  );
+  const tx1 = await program.rpc.transfer(new BN(10000000), [...reference], "invoice #1", {
     accounts: {
       sender: provider.wallet.publicKey,
       senderAssociate: associatedTokenAccount,
       receiver: toWallet.publicKey,
+      recipientEntry: recipientEntry,
       receiverAssociate: toATA,
       mint: mintAddress,
-      configAccount: configAccount.publicKey,
+      // treasury defaults to the admin until `set_fee` changes it
+      treasuryAssociate: associatedTokenAccount,
+      receiptAccount: receiptAccount,
+      policyAccount: policyAccount,
+      configAccount: configAccount,
       tokenProgram: TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
       associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
     },
     signers: [payer],
   });
@@ -7,6 +7,72 @@ pub enum ErrorDefine {
 
   #[msg("Unauthorized !!!")]
   Unauthorized,
+
+  #[msg("This mint address is already supported")]
+  MintAlreadySupported,
+
+  #[msg("Config supports at most 10 mints")]
+  TooManyMints,
+
+  #[msg("Batch needs one (receiver, token account) pair per amount, plus a recipient entry when receivers are restricted")]
+  InvalidBatch,
+
+  #[msg("Receiver token account has the wrong mint or owner")]
+  InvalidReceiver,
+
+  #[msg("Fee needs at most 10000 bps and bounds with min <= max")]
+  InvalidFee,
+
+  #[msg("Amount is smaller than the fee")]
+  AmountBelowFee,
+
+  #[msg("Treasury token account is not owned by the config treasury")]
+  InvalidTreasury,
+
+  #[msg("Grant needs a positive amount and duration and a cliff within the schedule")]
+  InvalidSchedule,
+
+  #[msg("Nothing has vested since the last claim")]
+  NothingToClaim,
+
+  #[msg("Grant is irrevocable or already revoked")]
+  NotRevocable,
+
+  #[msg("Stream needs a positive rate and deposit")]
+  InvalidStream,
+
+  #[msg("Nothing has accrued since the last withdraw")]
+  NothingToWithdraw,
+
+  #[msg("Native transfers need the wSOL mint in the config")]
+  NativeMintUnSupport,
+
+  #[msg("A receipt for this reference already exists")]
+  DuplicateReference,
+
+  #[msg("Receipt account is missing or not the PDA of the reference")]
+  InvalidReceipt,
+
+  #[msg("Memo is longer than 64 bytes")]
+  MemoTooLong,
+
+  #[msg("Policy window must be between 1 second and 1 year")]
+  InvalidPolicy,
+
+  #[msg("Transfer exceeds the sender policy limit for this window")]
+  PolicyLimitExceeded,
+
+  #[msg("Receiver is not on the recipient allowlist")]
+  ReceiverNotAllowlisted,
+
+  #[msg("Receiver is on the recipient blocklist")]
+  ReceiverBlocklisted,
+
+  #[msg("Recipient entry is not the PDA of the receiver")]
+  InvalidRecipientEntry,
+
+  #[msg("Sender wSOL balance is below the amount")]
+  InsufficientWrappedBalance,
 }
 
 impl From<ErrorDefine> for ProgramError {
@@ -1,69 +1,1185 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token::{self, Token};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 declare_id!("6uduzpBoMfGfcRMkFpd8u4PPDJayS8DijDtTXpGHYf1D");
 
 pub mod error;
+pub mod processor;
+pub mod state;
 use crate::error::ErrorDefine;
+use crate::processor::{
+    add_supported_mint, charge_native_fee, check_receiver_associate, check_recipient,
+    close_token_account, compute_fee, consume_policy, create_associate_if_needed, create_receipt,
+    harvest_withheld_fees, refresh_policy, sync_native, transfer_fee, transfer_lamports,
+    transfer_token,
+};
+use crate::state::{
+    Config, Fee, FeeBounds, FeeCharged, Grant, ListKind, Policy, PolicyLimit, Receipt, RecipientEntry,
+    RecipientMode, Stream, DEFAULT_POLICY_WINDOW, MAX_FEE_BPS, MAX_MEMO_LEN, MAX_POLICY_WINDOW,
+    POLICY_RAISE_DELAY,
+};
+
+const CONFIG_PDA_SEED: &[u8] = b"config";
+const GRANT_PDA_SEED: &[u8] = b"grant";
+const VAULT_PDA_SEED: &[u8] = b"vault";
+const STREAM_PDA_SEED: &[u8] = b"stream";
+const RECEIPT_PDA_SEED: &[u8] = b"receipt";
+const POLICY_PDA_SEED: &[u8] = b"policy";
+const RECIPIENT_PDA_SEED: &[u8] = b"recipient";
+
 #[program]
 pub mod transfer {
     use super::*;
 
-    pub fn init(ctx: Context<Create>, mint: Pubkey) -> Result<()> {
-        ctx.accounts.config_account.mint_address = mint;
+    pub fn init(ctx: Context<Create>, mints: Vec<Pubkey>) -> Result<()> {
+        let config = &mut ctx.accounts.config_account;
+        config.admin = ctx.accounts.admin.key();
+        config.mints = Vec::new();
+        config.bump = *ctx.bumps.get("config_account").unwrap();
+        config.fee = Fee::default();
+        config.treasury = ctx.accounts.admin.key();
+        config.recipient_mode = RecipientMode::Open;
+        config.fee_bounds = Vec::new();
+        for mint in mints {
+            add_supported_mint(config, mint)?;
+        }
+        Ok(())
+    }
+
+    pub fn add_mint(ctx: Context<AdminConfig>, mint: Pubkey) -> Result<()> {
+        add_supported_mint(&mut ctx.accounts.config_account, mint)
+    }
+
+    pub fn remove_mint(ctx: Context<AdminConfig>, mint: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config_account;
+        let index = config
+            .mints
+            .iter()
+            .position(|supported| *supported == mint)
+            .ok_or(ErrorDefine::MintUnSupport)?;
+        config.mints.remove(index);
+        config.fee_bounds.retain(|bounds| bounds.mint != mint);
+        Ok(())
+    }
+
+    pub fn set_admin(ctx: Context<AdminConfig>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.config_account.admin = new_admin;
+        Ok(())
+    }
+
+    pub fn set_fee(ctx: Context<AdminConfig>, fee: Fee, treasury: Pubkey) -> Result<()> {
+        require_gte!(MAX_FEE_BPS, fee.bps, ErrorDefine::InvalidFee);
+        let config = &mut ctx.accounts.config_account;
+        config.fee = fee;
+        config.treasury = treasury;
+        Ok(())
+    }
+
+    // bounds are in the base units of `mint`, [0, u64::MAX] leaves its fee unclamped
+    pub fn set_fee_bounds(ctx: Context<AdminConfig>, mint: Pubkey, min: u64, max: u64) -> Result<()> {
+        require_gte!(max, min, ErrorDefine::InvalidFee);
+        let config = &mut ctx.accounts.config_account;
+        require!(config.mints.contains(&mint), ErrorDefine::MintUnSupport);
+        config.fee_bounds.retain(|bounds| bounds.mint != mint);
+        config.fee_bounds.push(FeeBounds { mint, min, max });
+        Ok(())
+    }
+
+    pub fn set_recipient_mode(ctx: Context<AdminConfig>, mode: RecipientMode) -> Result<()> {
+        ctx.accounts.config_account.recipient_mode = mode;
+        Ok(())
+    }
+
+    // one entry per address, re-adding it switches the list it is on
+    pub fn add_recipient(
+        ctx: Context<AddRecipient>,
+        address: Pubkey,
+        kind: ListKind,
+    ) -> Result<()> {
+        let entry = &mut ctx.accounts.recipient_entry;
+        entry.address = address;
+        entry.kind = kind;
+        entry.bump = *ctx.bumps.get("recipient_entry").unwrap();
         Ok(())
     }
 
-    pub fn transfer(ctx: Context<TransferToken>, amount: u64) -> Result<()> {
-        let source = &ctx.accounts.sender_associate;
-        let destination = &ctx.accounts.receiver_associate;
-        let transfer_accounts = Transfer {
-            from: source.to_account_info(),
-            to: destination.to_account_info(),
-            authority: ctx.accounts.sender.to_account_info(),
+    pub fn remove_recipient(_ctx: Context<RemoveRecipient>, _address: Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    // `reference` makes the payment idempotent: it creates the receipt PDA of
+    // (sender, reference) and fails when that receipt already exists.
+    // The protocol fee is a separate transfer to the treasury, so a Token-2022 mint
+    // with a transfer fee charges its fee on both transfers.
+    pub fn transfer(
+        ctx: Context<TransferToken>,
+        amount: u64,
+        reference: Option<[u8; 32]>,
+        memo: Option<String>,
+    ) -> Result<()> {
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.receiver.key().as_ref()],
+        )?;
+        let memo = memo.unwrap_or_default();
+        require_gte!(MAX_MEMO_LEN, memo.len(), ErrorDefine::MemoTooLong);
+        let fee = compute_fee(&ctx.accounts.config_account, ctx.accounts.mint.key(), amount)?;
+        let withheld = transfer_fee(&ctx.accounts.mint, amount - fee)?;
+        let received = amount - fee - withheld;
+        if let Some(reference) = reference {
+            let receipt_account = ctx
+                .accounts
+                .receipt_account
+                .as_ref()
+                .ok_or(ErrorDefine::InvalidReceipt)?;
+            let sender = ctx.accounts.sender.key();
+            create_receipt(
+                receipt_account.to_account_info(),
+                ctx.accounts.sender.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                &[RECEIPT_PDA_SEED, sender.as_ref(), &reference],
+                Receipt {
+                    sender,
+                    reference,
+                    receiver: ctx.accounts.receiver.key(),
+                    mint: ctx.accounts.mint.key(),
+                    amount: received,
+                    slot: Clock::get()?.slot,
+                    memo,
+                    bump: 0,
+                },
+            )?;
+        } else if !memo.is_empty() {
+            msg!("Memo: {}", memo);
+        }
+        create_associate_if_needed(
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            ctx.accounts.receiver_associate.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+        transfer_token(
+            ctx.accounts.sender_associate.to_account_info(),
+            ctx.accounts.receiver_associate.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.mint,
+            amount - fee,
+            ctx.accounts.token_program.to_account_info(),
+            None,
+        )?;
+        if fee > 0 {
+            transfer_token(
+                ctx.accounts.sender_associate.to_account_info(),
+                ctx.accounts.treasury_associate.to_account_info(),
+                ctx.accounts.sender.to_account_info(),
+                &ctx.accounts.mint,
+                fee,
+                ctx.accounts.token_program.to_account_info(),
+                None,
+            )?;
+        }
+        emit!(FeeCharged {
+            sender: ctx.accounts.sender.key(),
+            receiver: ctx.accounts.receiver.key(),
+            mint: ctx.accounts.mint.key(),
+            amount: received,
+            fee,
+            withheld,
+        });
+        Ok(())
+    }
+
+    // remaining accounts hold one (receiver, receiver associated token account) pair per
+    // amount, followed by the receiver's recipient entry when the config restricts receivers
+    pub fn transfer_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, TransferBatch<'info>>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let restricted = ctx.accounts.config_account.recipient_mode != RecipientMode::Open;
+        let stride = if restricted { 3 } else { 2 };
+        require!(
+            !amounts.is_empty() && ctx.remaining_accounts.len() == amounts.len() * stride,
+            ErrorDefine::InvalidBatch
+        );
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(ErrorDefine::InvalidBatch)?;
+        consume_policy(&ctx.accounts.policy_account, total)?;
+        let mint = ctx.accounts.mint.key();
+        let mut total_fee: u64 = 0;
+        for (receiver, amount) in ctx.remaining_accounts.chunks(stride).zip(amounts) {
+            check_receiver_associate(&receiver[0], &receiver[1], mint)?;
+            if restricted {
+                check_recipient(
+                    &ctx.accounts.config_account,
+                    &receiver[2],
+                    &[RECIPIENT_PDA_SEED, receiver[0].key().as_ref()],
+                )?;
+            }
+            let fee = compute_fee(&ctx.accounts.config_account, mint, amount)?;
+            let withheld = transfer_fee(&ctx.accounts.mint, amount - fee)?;
+            transfer_token(
+                ctx.accounts.sender_associate.to_account_info(),
+                receiver[1].clone(),
+                ctx.accounts.sender.to_account_info(),
+                &ctx.accounts.mint,
+                amount - fee,
+                ctx.accounts.token_program.to_account_info(),
+                None,
+            )?;
+            emit!(FeeCharged {
+                sender: ctx.accounts.sender.key(),
+                receiver: receiver[0].key(),
+                mint,
+                amount: amount - fee - withheld,
+                fee,
+                withheld,
+            });
+            total_fee += fee;
+        }
+        // collect the fee of the whole batch in one transfer
+        if total_fee > 0 {
+            transfer_token(
+                ctx.accounts.sender_associate.to_account_info(),
+                ctx.accounts.treasury_associate.to_account_info(),
+                ctx.accounts.sender.to_account_info(),
+                &ctx.accounts.mint,
+                total_fee,
+                ctx.accounts.token_program.to_account_info(),
+                None,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn transfer_native(ctx: Context<TransferNative>, amount: u64) -> Result<()> {
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.receiver.key().as_ref()],
+        )?;
+        let fee = charge_native_fee(
+            &ctx.accounts.config_account,
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            amount,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        transfer_lamports(
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            amount - fee,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        emit!(FeeCharged {
+            sender: ctx.accounts.sender.key(),
+            receiver: ctx.accounts.receiver.key(),
+            mint: native_mint::ID,
+            amount: amount - fee,
+            fee,
+            withheld: 0,
+        });
+        Ok(())
+    }
+
+    // pays the receiver in wSOL so it does not need to handle SOL separately
+    pub fn transfer_native_wrapped(ctx: Context<TransferNativeWrapped>, amount: u64) -> Result<()> {
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.receiver.key().as_ref()],
+        )?;
+        let fee = charge_native_fee(
+            &ctx.accounts.config_account,
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            amount,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        transfer_lamports(
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.receiver_associate.to_account_info(),
+            amount - fee,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        sync_native(
+            ctx.accounts.receiver_associate.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+        )?;
+        emit!(FeeCharged {
+            sender: ctx.accounts.sender.key(),
+            receiver: ctx.accounts.receiver.key(),
+            mint: native_mint::ID,
+            amount: amount - fee,
+            fee,
+            withheld: 0,
+        });
+        Ok(())
+    }
+
+    // closes the sender's wSOL account back into SOL, then pays the receiver in SOL.
+    // The whole wSOL balance is unwrapped, whatever is left over after `amount` stays
+    // with the sender as SOL.
+    pub fn transfer_native_unwrapped(
+        ctx: Context<TransferNativeUnwrapped>,
+        amount: u64,
+    ) -> Result<()> {
+        // the payment has to be covered by wSOL, not by the sender's own SOL
+        require_gte!(
+            ctx.accounts.sender_associate.amount,
+            amount,
+            ErrorDefine::InsufficientWrappedBalance
+        );
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.receiver.key().as_ref()],
+        )?;
+        close_token_account(
+            ctx.accounts.sender_associate.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            &[],
+        )?;
+        let fee = charge_native_fee(
+            &ctx.accounts.config_account,
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            amount,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        transfer_lamports(
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            amount - fee,
+            ctx.accounts.system_program.to_account_info(),
+        )?;
+        emit!(FeeCharged {
+            sender: ctx.accounts.sender.key(),
+            receiver: ctx.accounts.receiver.key(),
+            mint: native_mint::ID,
+            amount: amount - fee,
+            fee,
+            withheld: 0,
+        });
+        Ok(())
+    }
+
+    // tightening applies at once, anything looser waits `POLICY_RAISE_DELAY` seconds
+    pub fn set_policy(ctx: Context<SetPolicy>, limit: u64, window: Option<u64>) -> Result<()> {
+        let new_limit = PolicyLimit {
+            limit,
+            window: window.unwrap_or(DEFAULT_POLICY_WINDOW),
         };
-        // create cpi context for transfer
-        let cpi_ctx = CpiContext::new(
+        require!(
+            (1..=MAX_POLICY_WINDOW).contains(&new_limit.window),
+            ErrorDefine::InvalidPolicy
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let policy = &mut ctx.accounts.policy_account;
+        if policy.owner == Pubkey::default() {
+            policy.owner = ctx.accounts.owner.key();
+            policy.mint = ctx.accounts.mint.key();
+            policy.current = new_limit;
+            policy.pending = None;
+            policy.window_start = now;
+            policy.used = 0;
+            policy.bump = *ctx.bumps.get("policy_account").unwrap();
+            return Ok(());
+        }
+        refresh_policy(policy, now);
+        if new_limit.is_tighter_than(&policy.current) {
+            policy.current = new_limit;
+            policy.pending = None;
+        } else {
+            policy.pending = Some(new_limit);
+            policy.pending_at = now + POLICY_RAISE_DELAY;
+        }
+        Ok(())
+    }
+
+    pub fn create_grant(
+        ctx: Context<CreateGrant>,
+        id: u64,
+        amount: u64,
+        start_ts: i64,
+        cliff_ts: i64,
+        duration: u64,
+        revoker: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            amount > 0
+                && duration > 0
+                && duration <= i64::MAX as u64
+                && start_ts <= cliff_ts
+                && cliff_ts <= start_ts.saturating_add(duration as i64),
+            ErrorDefine::InvalidSchedule
+        );
+        // a grant can vest at once, so it counts like a transfer to the beneficiary
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.beneficiary.key().as_ref()],
+        )?;
+        // the vault holds `amount` minus the Token-2022 transfer fee, if any
+        let received = amount - transfer_fee(&ctx.accounts.mint, amount)?;
+        require_gt!(received, 0, ErrorDefine::InvalidSchedule);
+        let grant = &mut ctx.accounts.grant_account;
+        grant.grantor = ctx.accounts.grantor.key();
+        grant.beneficiary = ctx.accounts.beneficiary.key();
+        grant.mint = ctx.accounts.mint.key();
+        grant.id = id;
+        grant.revoker = revoker;
+        grant.amount = received;
+        grant.claimed = 0;
+        grant.start_ts = start_ts;
+        grant.cliff_ts = cliff_ts;
+        grant.duration = duration;
+        grant.revoked = false;
+        grant.bump = *ctx.bumps.get("grant_account").unwrap();
+        grant.vault_bump = *ctx.bumps.get("vault").unwrap();
+        transfer_token(
+            ctx.accounts.grantor_associate.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.grantor.to_account_info(),
+            &ctx.accounts.mint,
+            amount,
+            ctx.accounts.token_program.to_account_info(),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn claim(ctx: Context<ClaimGrant>) -> Result<()> {
+        create_associate_if_needed(
+            ctx.accounts.beneficiary.to_account_info(),
+            ctx.accounts.beneficiary.to_account_info(),
+            ctx.accounts.beneficiary_associate.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+        let grant = &mut ctx.accounts.grant_account;
+        let now = Clock::get()?.unix_timestamp;
+        let claimable = grant.vested(now) - grant.claimed;
+        require_gt!(claimable, 0, ErrorDefine::NothingToClaim);
+        grant.claimed += claimable;
+
+        let id = grant.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[
+            GRANT_PDA_SEED,
+            grant.grantor.as_ref(),
+            grant.beneficiary.as_ref(),
+            grant.mint.as_ref(),
+            &id,
+            &[grant.bump],
+        ];
+        transfer_token(
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.beneficiary_associate.to_account_info(),
+            grant.to_account_info(),
+            &ctx.accounts.mint,
+            claimable,
             ctx.accounts.token_program.to_account_info(),
-            transfer_accounts,
+            Some(&[seeds]),
+        )?;
+        Ok(())
+    }
+
+    // the beneficiary keeps what has vested so far, the rest goes back to the grantor
+    pub fn revoke(ctx: Context<RevokeGrant>) -> Result<()> {
+        let grant = &mut ctx.accounts.grant_account;
+        require!(
+            !grant.revoked && grant.revoker == Some(ctx.accounts.revoker.key()),
+            ErrorDefine::NotRevocable
         );
-        // transfer
-        anchor_spl::token::transfer(cpi_ctx, amount)?;
+        let now = Clock::get()?.unix_timestamp;
+        let vested = grant.vested(now);
+        let unvested = grant.amount - vested;
+        grant.amount = vested;
+        grant.revoked = true;
+
+        if unvested > 0 {
+            let id = grant.id.to_le_bytes();
+            let seeds: &[&[u8]] = &[
+                GRANT_PDA_SEED,
+                grant.grantor.as_ref(),
+                grant.beneficiary.as_ref(),
+                grant.mint.as_ref(),
+                &id,
+                &[grant.bump],
+            ];
+            transfer_token(
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.grantor_associate.to_account_info(),
+                grant.to_account_info(),
+                &ctx.accounts.mint,
+                unvested,
+                ctx.accounts.token_program.to_account_info(),
+                Some(&[seeds]),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn create_stream(
+        ctx: Context<CreateStream>,
+        id: u64,
+        rate: u64,
+        deposit: u64,
+    ) -> Result<()> {
+        require!(rate > 0 && deposit > 0, ErrorDefine::InvalidStream);
+        // a high rate pays out the deposit at once, so it counts like a transfer
+        consume_policy(&ctx.accounts.policy_account, deposit)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.recipient.key().as_ref()],
+        )?;
+        // the vault holds `deposit` minus the Token-2022 transfer fee, if any
+        let received = deposit - transfer_fee(&ctx.accounts.mint, deposit)?;
+        require_gt!(received, 0, ErrorDefine::InvalidStream);
+        let stream = &mut ctx.accounts.stream_account;
+        stream.sender = ctx.accounts.sender.key();
+        stream.recipient = ctx.accounts.recipient.key();
+        stream.mint = ctx.accounts.mint.key();
+        stream.id = id;
+        stream.rate = rate;
+        stream.deposited = received;
+        stream.withdrawn = 0;
+        stream.checkpoint_ts = Clock::get()?.unix_timestamp;
+        stream.checkpoint_accrued = 0;
+        stream.bump = *ctx.bumps.get("stream_account").unwrap();
+        stream.vault_bump = *ctx.bumps.get("vault").unwrap();
+        transfer_token(
+            ctx.accounts.sender_associate.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.mint,
+            deposit,
+            ctx.accounts.token_program.to_account_info(),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn top_up(ctx: Context<TopUpStream>, amount: u64) -> Result<()> {
+        require_gt!(amount, 0, ErrorDefine::InvalidStream);
+        consume_policy(&ctx.accounts.policy_account, amount)?;
+        check_recipient(
+            &ctx.accounts.config_account,
+            &ctx.accounts.recipient_entry,
+            &[RECIPIENT_PDA_SEED, ctx.accounts.stream_account.recipient.as_ref()],
+        )?;
+        let received = amount - transfer_fee(&ctx.accounts.mint, amount)?;
+        let stream = &mut ctx.accounts.stream_account;
+        let now = Clock::get()?.unix_timestamp;
+        stream.checkpoint_accrued = stream.accrued(now);
+        stream.checkpoint_ts = now;
+        stream.deposited = stream
+            .deposited
+            .checked_add(received)
+            .ok_or(ErrorDefine::InvalidStream)?;
+        transfer_token(
+            ctx.accounts.sender_associate.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.mint,
+            amount,
+            ctx.accounts.token_program.to_account_info(),
+            None,
+        )?;
         Ok(())
     }
-}
 
-#[account]
-pub struct Config {
-    mint_address: Pubkey,
+    pub fn withdraw(ctx: Context<WithdrawStream>) -> Result<()> {
+        create_associate_if_needed(
+            ctx.accounts.recipient.to_account_info(),
+            ctx.accounts.recipient.to_account_info(),
+            ctx.accounts.recipient_associate.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+        let stream = &mut ctx.accounts.stream_account;
+        let available = stream.accrued(Clock::get()?.unix_timestamp) - stream.withdrawn;
+        require_gt!(available, 0, ErrorDefine::NothingToWithdraw);
+        stream.withdrawn += available;
+
+        let id = stream.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[
+            STREAM_PDA_SEED,
+            stream.sender.as_ref(),
+            stream.recipient.as_ref(),
+            stream.mint.as_ref(),
+            &id,
+            &[stream.bump],
+        ];
+        transfer_token(
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.recipient_associate.to_account_info(),
+            stream.to_account_info(),
+            &ctx.accounts.mint,
+            available,
+            ctx.accounts.token_program.to_account_info(),
+            Some(&[seeds]),
+        )?;
+        Ok(())
+    }
+
+    // pays out what has accrued, refunds the rest and closes the stream
+    pub fn cancel_stream(ctx: Context<CancelStream>) -> Result<()> {
+        create_associate_if_needed(
+            ctx.accounts.sender.to_account_info(),
+            ctx.accounts.recipient.to_account_info(),
+            ctx.accounts.recipient_associate.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+        let stream = &ctx.accounts.stream_account;
+        let accrued = stream.accrued(Clock::get()?.unix_timestamp);
+        let owed = accrued - stream.withdrawn;
+        let refund = stream.deposited - accrued;
+
+        let id = stream.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[
+            STREAM_PDA_SEED,
+            stream.sender.as_ref(),
+            stream.recipient.as_ref(),
+            stream.mint.as_ref(),
+            &id,
+            &[stream.bump],
+        ];
+        if owed > 0 {
+            transfer_token(
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.recipient_associate.to_account_info(),
+                stream.to_account_info(),
+                &ctx.accounts.mint,
+                owed,
+                ctx.accounts.token_program.to_account_info(),
+                Some(&[seeds]),
+            )?;
+        }
+        if refund > 0 {
+            transfer_token(
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.sender_associate.to_account_info(),
+                stream.to_account_info(),
+                &ctx.accounts.mint,
+                refund,
+                ctx.accounts.token_program.to_account_info(),
+                Some(&[seeds]),
+            )?;
+        }
+        // fees withheld on deposits into the vault would block closing it
+        harvest_withheld_fees(
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+        )?;
+        close_token_account(
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.sender.to_account_info(),
+            stream.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            &[seeds],
+        )?;
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
 pub struct TransferToken<'info> {
     #[account(mut, constraint = sender.data_is_empty() && sender.lamports() > 0)]
     pub sender: Signer<'info>,
-    #[account(mut, associated_token::mint = mint, associated_token::authority = sender)]
-    pub sender_associate: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program
+    )]
+    pub sender_associate: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: system account per the `data_is_empty` constraint, only receives tokens or lamports
     #[account(mut, constraint = receiver.data_is_empty())]
     pub receiver: AccountInfo<'info>,
+    // recipient list PDA of the receiver, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
 
-    #[account(mut, associated_token::mint = mint, associated_token::authority = receiver)]
-    pub receiver_associate: Account<'info, TokenAccount>,
+    // created and paid for by the sender when the receiver has none yet
+    /// CHECK: address checked by `create_associate_if_needed`
+    #[account(mut)]
+    pub receiver_associate: AccountInfo<'info>,
+    #[account(seeds = [CONFIG_PDA_SEED], bump = config_account.bump)]
     pub config_account: Account<'info, Config>,
 
-    #[account(address = config_account.mint_address @ ErrorDefine::MintUnSupport)]
-    pub mint: Account<'info, Mint>,
+    #[account(constraint = config_account.mints.contains(&mint.key()) @ ErrorDefine::MintUnSupport)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = treasury_associate.owner == config_account.treasury @ ErrorDefine::InvalidTreasury
+    )]
+    pub treasury_associate: InterfaceAccount<'info, TokenAccount>,
+    // PDA of (sender, reference), only needed when `transfer` gets a reference
+    /// CHECK: address and emptiness checked by `create_receipt`
+    #[account(mut)]
+    pub receipt_account: Option<UncheckedAccount<'info>>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), mint.key().as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct TransferBatch<'info> {
+    #[account(mut, constraint = sender.data_is_empty() && sender.lamports() > 0)]
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program
+    )]
+    pub sender_associate: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CONFIG_PDA_SEED], bump = config_account.bump)]
+    pub config_account: Account<'info, Config>,
+
+    #[account(constraint = config_account.mints.contains(&mint.key()) @ ErrorDefine::MintUnSupport)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = treasury_associate.owner == config_account.treasury @ ErrorDefine::InvalidTreasury
+    )]
+    pub treasury_associate: InterfaceAccount<'info, TokenAccount>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), mint.key().as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct TransferNative<'info> {
+    #[account(mut, constraint = sender.data_is_empty() && sender.lamports() > 0)]
+    pub sender: Signer<'info>,
+    /// CHECK: system account per the `data_is_empty` constraint, only receives tokens or lamports
+    #[account(mut, constraint = receiver.data_is_empty())]
+    pub receiver: AccountInfo<'info>,
+    // recipient list PDA of the receiver, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+    #[account(
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        constraint = config_account.mints.contains(&native_mint::ID) @ ErrorDefine::NativeMintUnSupport
+    )]
+    pub config_account: Account<'info, Config>,
+    /// CHECK: checked against the config treasury by the address constraint
+    #[account(mut, address = config_account.treasury @ ErrorDefine::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), native_mint::ID.as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferNativeWrapped<'info> {
+    #[account(mut, constraint = sender.data_is_empty() && sender.lamports() > 0)]
+    pub sender: Signer<'info>,
+    /// CHECK: system account per the `data_is_empty` constraint, only receives tokens or lamports
+    #[account(constraint = receiver.data_is_empty())]
+    pub receiver: AccountInfo<'info>,
+    // recipient list PDA of the receiver, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+
+    // created and paid for by the sender when the receiver has none yet
+    #[account(
+        init_if_needed,
+        payer = sender,
+        associated_token::mint = native_mint,
+        associated_token::authority = receiver
+    )]
+    pub receiver_associate: Account<'info, token::TokenAccount>,
+    #[account(
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        constraint = config_account.mints.contains(&native_mint::ID) @ ErrorDefine::NativeMintUnSupport
+    )]
+    pub config_account: Account<'info, Config>,
+    /// CHECK: checked against the config treasury by the address constraint
+    #[account(mut, address = config_account.treasury @ ErrorDefine::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Account<'info, token::Mint>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), native_mint::ID.as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct TransferNativeUnwrapped<'info> {
+    #[account(mut, constraint = sender.data_is_empty() && sender.lamports() > 0)]
+    pub sender: Signer<'info>,
+    #[account(mut, associated_token::mint = native_mint::ID, associated_token::authority = sender)]
+    pub sender_associate: Account<'info, token::TokenAccount>,
+    /// CHECK: system account per the `data_is_empty` constraint, only receives tokens or lamports
+    #[account(mut, constraint = receiver.data_is_empty())]
+    pub receiver: AccountInfo<'info>,
+    // recipient list PDA of the receiver, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+    #[account(
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        constraint = config_account.mints.contains(&native_mint::ID) @ ErrorDefine::NativeMintUnSupport
+    )]
+    pub config_account: Account<'info, Config>,
+    /// CHECK: checked against the config treasury by the address constraint
+    #[account(mut, address = config_account.treasury @ ErrorDefine::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), native_mint::ID.as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct Create<'info> {
-    #[account(init, payer = user, space = 40)]
+    #[account(init, payer = admin, space = Config::LEN, seeds = [CONFIG_PDA_SEED], bump)]
     pub config_account: Account<'info, Config>,
+    // only the upgrade authority can claim the singleton config, so `init` can't be front-run
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Transfer>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorDefine::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+pub struct AdminConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        has_one = admin @ ErrorDefine::Unauthorized
+    )]
+    pub config_account: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateGrant<'info> {
+    #[account(mut)]
+    pub grantor: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = grantor,
+        token::token_program = token_program
+    )]
+    pub grantor_associate: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: only stored on the grant and used as seed
+    pub beneficiary: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = grantor,
+        space = Grant::LEN,
+        seeds = [GRANT_PDA_SEED, grantor.key().as_ref(), beneficiary.key().as_ref(), mint.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub grant_account: Account<'info, Grant>,
+    #[account(
+        init,
+        payer = grantor,
+        token::mint = mint,
+        token::authority = grant_account,
+        token::token_program = token_program,
+        seeds = [VAULT_PDA_SEED, grant_account.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CONFIG_PDA_SEED], bump = config_account.bump)]
+    pub config_account: Account<'info, Config>,
+
+    #[account(constraint = config_account.mints.contains(&mint.key()) @ ErrorDefine::MintUnSupport)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    // recipient list PDA of the beneficiary, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+    // grantor policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, grantor.key().as_ref(), mint.key().as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimGrant<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    // created on demand, see `create_associate_if_needed`
+    /// CHECK: address checked by `create_associate_if_needed`
+    #[account(mut)]
+    pub beneficiary_associate: AccountInfo<'info>,
+    #[account(mut, has_one = beneficiary @ ErrorDefine::Unauthorized, has_one = mint)]
+    pub grant_account: Account<'info, Grant>,
+    #[account(mut, seeds = [VAULT_PDA_SEED, grant_account.key().as_ref()], bump = grant_account.vault_bump)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeGrant<'info> {
+    pub revoker: Signer<'info>,
+    #[account(mut, has_one = grantor, has_one = mint)]
+    pub grant_account: Account<'info, Grant>,
+    #[account(mut, seeds = [VAULT_PDA_SEED, grant_account.key().as_ref()], bump = grant_account.vault_bump)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: checked against the grant by `has_one`
+    pub grantor: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = grantor,
+        token::token_program = token_program
+    )]
+    pub grantor_associate: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateStream<'info> {
+    #[account(mut)]
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program
+    )]
+    pub sender_associate: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: only stored on the stream and used as seed
+    pub recipient: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = sender,
+        space = Stream::LEN,
+        seeds = [STREAM_PDA_SEED, sender.key().as_ref(), recipient.key().as_ref(), mint.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub stream_account: Account<'info, Stream>,
+    #[account(
+        init,
+        payer = sender,
+        token::mint = mint,
+        token::authority = stream_account,
+        token::token_program = token_program,
+        seeds = [VAULT_PDA_SEED, stream_account.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CONFIG_PDA_SEED], bump = config_account.bump)]
+    pub config_account: Account<'info, Config>,
+
+    #[account(constraint = config_account.mints.contains(&mint.key()) @ ErrorDefine::MintUnSupport)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    // recipient list PDA of the recipient, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), mint.key().as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpStream<'info> {
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program
+    )]
+    pub sender_associate: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, has_one = sender @ ErrorDefine::Unauthorized, has_one = mint)]
+    pub stream_account: Account<'info, Stream>,
+    #[account(mut, seeds = [VAULT_PDA_SEED, stream_account.key().as_ref()], bump = stream_account.vault_bump)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CONFIG_PDA_SEED], bump = config_account.bump)]
+    pub config_account: Account<'info, Config>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    // recipient list PDA of the stream recipient, only read when the config restricts receivers
+    /// CHECK: address checked by `check_recipient` before it is read
+    pub recipient_entry: AccountInfo<'info>,
+    // sender policy, enforced when it exists
+    /// CHECK: PDA per the seeds constraint, parsed by `consume_policy` once it exists
+    #[account(mut, seeds = [POLICY_PDA_SEED, sender.key().as_ref(), mint.key().as_ref()], bump)]
+    pub policy_account: AccountInfo<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStream<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    // created on demand, see `create_associate_if_needed`
+    /// CHECK: address checked by `create_associate_if_needed`
+    #[account(mut)]
+    pub recipient_associate: AccountInfo<'info>,
+    #[account(mut, has_one = recipient @ ErrorDefine::Unauthorized, has_one = mint)]
+    pub stream_account: Account<'info, Stream>,
+    #[account(mut, seeds = [VAULT_PDA_SEED, stream_account.key().as_ref()], bump = stream_account.vault_bump)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CancelStream<'info> {
+    #[account(mut)]
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program
+    )]
+    pub sender_associate: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: checked against the stream by `has_one`
+    pub recipient: AccountInfo<'info>,
+    // created and paid for by the sender when the recipient has none yet
+    /// CHECK: address checked by `create_associate_if_needed`
+    #[account(mut)]
+    pub recipient_associate: AccountInfo<'info>,
+    #[account(
+        mut,
+        close = sender,
+        has_one = sender @ ErrorDefine::Unauthorized,
+        has_one = recipient,
+        has_one = mint
+    )]
+    pub stream_account: Account<'info, Stream>,
+    #[account(mut, seeds = [VAULT_PDA_SEED, stream_account.key().as_ref()], bump = stream_account.vault_bump)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    // writable so withheld Token-2022 fees can be harvested into it
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetPolicy<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = Policy::LEN,
+        seeds = [POLICY_PDA_SEED, owner.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub policy_account: Account<'info, Policy>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(address: Pubkey)]
+pub struct AddRecipient<'info> {
+    #[account(
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        has_one = admin @ ErrorDefine::Unauthorized
+    )]
+    pub config_account: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = RecipientEntry::LEN,
+        seeds = [RECIPIENT_PDA_SEED, address.as_ref()],
+        bump
+    )]
+    pub recipient_entry: Account<'info, RecipientEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(address: Pubkey)]
+pub struct RemoveRecipient<'info> {
+    #[account(
+        seeds = [CONFIG_PDA_SEED],
+        bump = config_account.bump,
+        has_one = admin @ ErrorDefine::Unauthorized
+    )]
+    pub config_account: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [RECIPIENT_PDA_SEED, address.as_ref()],
+        bump = recipient_entry.bump
+    )]
+    pub recipient_entry: Account<'info, RecipientEntry>,
+}
@@ -0,0 +1,295 @@
+use crate::error::ErrorDefine;
+use crate::state::{
+  Config, ListKind, Policy, Receipt, RecipientEntry, RecipientMode, MAX_MINTS,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id};
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeAmount;
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use anchor_spl::token_interface::{CloseAccount, Mint, SyncNative, TokenAccount, TransferChecked};
+
+pub use token_fee::transfer_fee;
+
+pub fn add_supported_mint(config: &mut Config, mint: Pubkey) -> Result<()> {
+  require!(!config.mints.contains(&mint), ErrorDefine::MintAlreadySupported);
+  require_gt!(MAX_MINTS, config.mints.len(), ErrorDefine::TooManyMints);
+  config.mints.push(mint);
+  Ok(())
+}
+
+// transfer fungible token, through either the Token or the Token-2022 program
+pub fn transfer_token<'info>(
+  sender: AccountInfo<'info>,
+  receiver: AccountInfo<'info>,
+  user: AccountInfo<'info>,
+  mint: &InterfaceAccount<'info, Mint>,
+  amount: u64,
+  token_program: AccountInfo<'info>,
+  seeds: Option<&[&[&[u8]]]>,
+) -> Result<()> {
+  let transfer_instruction_account = TransferChecked {
+    from: sender.to_account_info(),
+    mint: mint.to_account_info(),
+    to: receiver.to_account_info(),
+    authority: user.to_account_info(),
+  };
+  let cpi_ctx = match seeds {
+    Some(seeds) => CpiContext::new_with_signer(
+      token_program.to_account_info(),
+      transfer_instruction_account,
+      seeds,
+    ),
+    None => CpiContext::new(token_program.to_account_info(), transfer_instruction_account),
+  };
+  anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
+  Ok(())
+}
+
+// `associated_token` constraints derive the address with the legacy Token program,
+// so associated accounts that may belong to Token-2022 are created here instead
+pub fn create_associate_if_needed<'info>(
+  payer: AccountInfo<'info>,
+  owner: AccountInfo<'info>,
+  associate: AccountInfo<'info>,
+  mint: AccountInfo<'info>,
+  token_program: AccountInfo<'info>,
+  system_program: AccountInfo<'info>,
+  associated_token_program: AccountInfo<'info>,
+) -> Result<()> {
+  let expected = get_associated_token_address_with_program_id(owner.key, mint.key, token_program.key);
+  require_keys_eq!(associate.key(), expected, ErrorDefine::InvalidReceiver);
+  if associate.data_is_empty() {
+    let create_instruction_account = associated_token::Create {
+      payer,
+      associated_token: associate,
+      authority: owner,
+      mint,
+      system_program,
+      token_program,
+    };
+    associated_token::create(CpiContext::new(
+      associated_token_program,
+      create_instruction_account,
+    ))?;
+  }
+  Ok(())
+}
+
+// transfer lamports from a system owned signer
+pub fn transfer_lamports<'info>(
+  sender: AccountInfo<'info>,
+  receiver: AccountInfo<'info>,
+  amount: u64,
+  system_program: AccountInfo<'info>,
+) -> Result<()> {
+  let transfer_instruction_account = system_program::Transfer {
+    from: sender,
+    to: receiver,
+  };
+  system_program::transfer(
+    CpiContext::new(system_program, transfer_instruction_account),
+    amount,
+  )
+}
+
+// bring the token amount of a wSOL account in line with its lamports
+pub fn sync_native<'info>(account: AccountInfo<'info>, token_program: AccountInfo<'info>) -> Result<()> {
+  anchor_spl::token_interface::sync_native(CpiContext::new(token_program, SyncNative { account }))
+}
+
+// close a token account, its lamports (rent, plus the balance of a wSOL account) go to `destination`
+pub fn close_token_account<'info>(
+  account: AccountInfo<'info>,
+  destination: AccountInfo<'info>,
+  authority: AccountInfo<'info>,
+  token_program: AccountInfo<'info>,
+  seeds: &[&[&[u8]]],
+) -> Result<()> {
+  let close_instruction_account = CloseAccount {
+    account,
+    destination,
+    authority,
+  };
+  anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
+    token_program,
+    close_instruction_account,
+    seeds,
+  ))
+}
+
+// Token-2022 refuses to close an account that still holds withheld transfer fees,
+// so move them to the mint first. No-op for accounts without withheld fees.
+pub fn harvest_withheld_fees<'info>(
+  account: AccountInfo<'info>,
+  mint: AccountInfo<'info>,
+  token_program: AccountInfo<'info>,
+) -> Result<()> {
+  if *account.owner != spl_token_2022::ID {
+    return Ok(());
+  }
+  let withheld = {
+    let data = account.try_borrow_data()?;
+    let account_state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    match account_state.get_extension::<TransferFeeAmount>() {
+      Ok(fee_amount) => u64::from(fee_amount.withheld_amount),
+      Err(_) => 0,
+    }
+  };
+  if withheld == 0 {
+    return Ok(());
+  }
+  let instruction = harvest_withheld_tokens_to_mint(token_program.key, mint.key, &[account.key])?;
+  invoke(&instruction, &[mint, account, token_program])?;
+  Ok(())
+}
+
+// checks on the receivers `transfer_batch` gets as remaining accounts
+pub fn check_receiver_associate(
+  receiver: &AccountInfo,
+  receiver_associate: &AccountInfo,
+  mint: Pubkey,
+) -> Result<()> {
+  require!(receiver.data_is_empty(), ErrorDefine::InvalidReceiver);
+  require!(receiver_associate.is_writable, ErrorDefine::InvalidReceiver);
+  let token_account = InterfaceAccount::<TokenAccount>::try_from(receiver_associate)?;
+  require_keys_eq!(token_account.mint, mint, ErrorDefine::InvalidReceiver);
+  require_keys_eq!(token_account.owner, receiver.key(), ErrorDefine::InvalidReceiver);
+  Ok(())
+}
+
+// bounds are looked up per mint, a single range would be off by the decimals between mints
+pub fn compute_fee(config: &Config, mint: Pubkey, amount: u64) -> Result<u64> {
+  let mut fee_amount = (amount as u128 * config.fee.bps as u128 / 10_000) as u64;
+  if let Some(bounds) = config.fee_bounds.iter().find(|bounds| bounds.mint == mint) {
+    fee_amount = fee_amount.clamp(bounds.min, bounds.max);
+  }
+  require_gte!(amount, fee_amount, ErrorDefine::AmountBelowFee);
+  Ok(fee_amount)
+}
+
+// lamport version of the fee `transfer` charges, paid straight to the treasury wallet
+pub fn charge_native_fee<'info>(
+  config: &Config,
+  sender: AccountInfo<'info>,
+  treasury: AccountInfo<'info>,
+  amount: u64,
+  system_program: AccountInfo<'info>,
+) -> Result<u64> {
+  let fee = compute_fee(config, native_mint::ID, amount)?;
+  if fee > 0 {
+    transfer_lamports(sender, treasury, fee, system_program)?;
+  }
+  Ok(fee)
+}
+
+// create the receipt PDA derived from `seeds` (without bump), rejects a reference that was used before
+pub fn create_receipt<'info>(
+  receipt_account: AccountInfo<'info>,
+  payer: AccountInfo<'info>,
+  system_program: AccountInfo<'info>,
+  seeds: &[&[u8]],
+  mut receipt: Receipt,
+) -> Result<()> {
+  let (address, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+  require_keys_eq!(receipt_account.key(), address, ErrorDefine::InvalidReceipt);
+  require!(receipt_account.data_is_empty(), ErrorDefine::DuplicateReference);
+
+  let bump_seed = [bump];
+  let signer_seeds = [seeds, &[&bump_seed[..]]].concat();
+  let rent = Rent::get()?.minimum_balance(Receipt::LEN);
+  // someone may have sent lamports to the address already, so fund, allocate and assign
+  // separately instead of `create_account`
+  let missing = rent.saturating_sub(receipt_account.lamports());
+  if missing > 0 {
+    transfer_lamports(payer, receipt_account.clone(), missing, system_program.clone())?;
+  }
+  system_program::allocate(
+    CpiContext::new_with_signer(
+      system_program.clone(),
+      system_program::Allocate {
+        account_to_allocate: receipt_account.clone(),
+      },
+      &[&signer_seeds],
+    ),
+    Receipt::LEN as u64,
+  )?;
+  system_program::assign(
+    CpiContext::new_with_signer(
+      system_program,
+      system_program::Assign {
+        account_to_assign: receipt_account.clone(),
+      },
+      &[&signer_seeds],
+    ),
+    &crate::ID,
+  )?;
+
+  receipt.bump = bump;
+  let mut data = receipt_account.try_borrow_mut_data()?;
+  receipt.try_serialize(&mut &mut data[..])?;
+  Ok(())
+}
+
+// apply a pending limit that is due and start a new window once the current one ended
+pub fn refresh_policy(policy: &mut Policy, now: i64) {
+  if let Some(pending) = policy.pending {
+    if now >= policy.pending_at {
+      policy.current = pending;
+      policy.pending = None;
+    }
+  }
+  let window = i64::try_from(policy.current.window).unwrap_or(i64::MAX);
+  if now >= policy.window_start.saturating_add(window) {
+    policy.window_start = now;
+    policy.used = 0;
+  }
+}
+
+// count `amount` against the sender policy, senders without a policy are not limited
+pub fn consume_policy<'info>(policy_account: &AccountInfo<'info>, amount: u64) -> Result<()> {
+  if policy_account.data_is_empty() {
+    return Ok(());
+  }
+  let mut policy = Account::<Policy>::try_from(policy_account)?;
+  refresh_policy(&mut policy, Clock::get()?.unix_timestamp);
+  let used = policy
+    .used
+    .checked_add(amount)
+    .ok_or(ErrorDefine::PolicyLimitExceeded)?;
+  require_gte!(policy.current.limit, used, ErrorDefine::PolicyLimitExceeded);
+  policy.used = used;
+  policy.exit(&crate::ID)
+}
+
+// enforce the recipient mode of the config, `entry` is the recipient PDA derived from `seeds`
+pub fn check_recipient<'info>(
+  config: &Config,
+  entry: &AccountInfo<'info>,
+  seeds: &[&[u8]],
+) -> Result<()> {
+  if config.recipient_mode == RecipientMode::Open {
+    return Ok(());
+  }
+  let (address, _) = Pubkey::find_program_address(seeds, &crate::ID);
+  require_keys_eq!(entry.key(), address, ErrorDefine::InvalidRecipientEntry);
+  let kind = if entry.data_is_empty() {
+    None
+  } else {
+    Some(Account::<RecipientEntry>::try_from(entry)?.kind)
+  };
+  match config.recipient_mode {
+    RecipientMode::Allowlist => {
+      require!(kind == Some(ListKind::Allow), ErrorDefine::ReceiverNotAllowlisted)
+    }
+    RecipientMode::Blocklist => {
+      require!(kind != Some(ListKind::Block), ErrorDefine::ReceiverBlocklisted)
+    }
+    RecipientMode::Open => {}
+  }
+  Ok(())
+}
@@ -0,0 +1,345 @@
+use anchor_lang::prelude::*;
+
+pub const MAX_MINTS: usize = 10;
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+// protocol fee: `bps` of the amount, clamped to the `FeeBounds` of the mint if it has any
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Fee {
+  pub bps: u16,
+}
+
+impl Fee {
+  pub const LEN: usize = 2; // u16
+}
+
+// fee range of one mint, in that mint's base units (lamports for the wSOL mint)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct FeeBounds {
+  pub mint: Pubkey,
+  pub min: u64,
+  pub max: u64,
+}
+
+impl FeeBounds {
+  pub const LEN: usize = 32 // PubKey
+    + 2 * 8; // u64
+}
+
+// which receivers `transfer` accepts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum RecipientMode {
+  Open,
+  // only receivers with an `Allow` entry
+  Allowlist,
+  // every receiver without a `Block` entry
+  Blocklist,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum ListKind {
+  Allow,
+  Block,
+}
+
+#[account]
+pub struct Config {
+  pub admin: Pubkey,
+  // mints `transfer` accepts
+  pub mints: Vec<Pubkey>,
+  pub bump: u8,
+  pub fee: Fee,
+  // wallet whose associated token accounts collect the fee of each mint
+  pub treasury: Pubkey,
+  pub recipient_mode: RecipientMode,
+  // at most one entry per supported mint, mints without one are not clamped
+  pub fee_bounds: Vec<FeeBounds>,
+}
+
+impl Config {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 4 + MAX_MINTS * 32 // Vec<PubKey>
+    + 1 // u8
+    + Fee::LEN
+    + 32 // PubKey
+    + 1 // RecipientMode
+    + 4 + MAX_MINTS * FeeBounds::LEN; // Vec<FeeBounds>
+}
+
+// admin managed list entry of one receiver address
+#[account]
+pub struct RecipientEntry {
+  pub address: Pubkey,
+  pub kind: ListKind,
+  pub bump: u8,
+}
+
+impl RecipientEntry {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 1 // ListKind
+    + 1; // u8
+}
+
+pub const DEFAULT_POLICY_WINDOW: u64 = 86_400;
+// longest window `set_policy` accepts, one year
+pub const MAX_POLICY_WINDOW: u64 = 365 * 86_400;
+// seconds before a looser policy limit takes effect
+pub const POLICY_RAISE_DELAY: i64 = 86_400;
+
+// at most `limit` of a mint per `window` seconds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq)]
+pub struct PolicyLimit {
+  pub limit: u64,
+  pub window: u64,
+}
+
+impl PolicyLimit {
+  pub const LEN: usize = 2 * 8; // u64
+
+  // a limit is only tighter if it allows less over at least as long a window
+  pub fn is_tighter_than(&self, other: &PolicyLimit) -> bool {
+    self.limit <= other.limit && self.window >= other.window
+  }
+}
+
+// spending cap of `owner` on one mint, checked by every transfer once it exists
+#[account]
+pub struct Policy {
+  pub owner: Pubkey,
+  pub mint: Pubkey,
+  pub current: PolicyLimit,
+  // looser limit waiting for `pending_at`
+  pub pending: Option<PolicyLimit>,
+  pub pending_at: i64,
+  // usage of the current window
+  pub window_start: i64,
+  pub used: u64,
+  pub bump: u8,
+}
+
+impl Policy {
+  pub const LEN: usize = 8 // internal discriminator
+    + 2 * 32 // PubKey
+    + PolicyLimit::LEN
+    + 1 + PolicyLimit::LEN // Option<PolicyLimit>
+    + 2 * 8 // i64
+    + 8 // u64
+    + 1; // u8
+}
+
+pub const MAX_MEMO_LEN: usize = 64;
+
+// proof of a referenced `transfer`, one per (sender, reference) so a payment can't be made twice
+#[account]
+pub struct Receipt {
+  pub sender: Pubkey,
+  pub reference: [u8; 32],
+  pub receiver: Pubkey,
+  pub mint: Pubkey,
+  // what the receiver got, after the protocol fee and any Token-2022 transfer fee
+  pub amount: u64,
+  pub slot: u64,
+  pub memo: String,
+  pub bump: u8,
+}
+
+impl Receipt {
+  pub const LEN: usize = 8 // internal discriminator
+    + 32 // PubKey
+    + 32 // [u8; 32]
+    + 2 * 32 // PubKey
+    + 2 * 8 // u64
+    + 4 + MAX_MEMO_LEN // String
+    + 1; // u8
+}
+
+#[event]
+pub struct FeeCharged {
+  pub sender: Pubkey,
+  pub receiver: Pubkey,
+  pub mint: Pubkey,
+  // what the receiver got, the sender paid `amount + fee + withheld`
+  pub amount: u64,
+  // protocol fee sent to the treasury, which receives it minus the mint's transfer fee
+  pub fee: u64,
+  // Token-2022 transfer fee withheld from the receiver's transfer, 0 for other mints
+  pub withheld: u64,
+}
+
+// tokens locked in a vault for `beneficiary`, released linearly from `start_ts`
+// over `duration` seconds with nothing claimable before `cliff_ts`
+#[account]
+pub struct Grant {
+  pub grantor: Pubkey,
+  pub beneficiary: Pubkey,
+  pub mint: Pubkey,
+  pub id: u64,
+  // key allowed to take back the unvested part, `None` is irrevocable
+  pub revoker: Option<Pubkey>,
+  pub amount: u64,
+  pub claimed: u64,
+  pub start_ts: i64,
+  pub cliff_ts: i64,
+  pub duration: u64,
+  // once revoked `amount` is cut to what had vested at that time
+  pub revoked: bool,
+  pub bump: u8,
+  pub vault_bump: u8,
+}
+
+impl Grant {
+  pub const LEN: usize = 8 // internal discriminator
+    + 3 * 32 // PubKey
+    + 8 // u64
+    + 1 + 32 // Option<PubKey>
+    + 2 * 8 // u64
+    + 2 * 8 // i64
+    + 8 // u64
+    + 1 // bool
+    + 2; // u8
+
+  pub fn vested(&self, now: i64) -> u64 {
+    if self.revoked || now >= self.start_ts.saturating_add(self.duration as i64) {
+      return self.amount;
+    }
+    if now < self.cliff_ts || now <= self.start_ts {
+      return 0;
+    }
+    let elapsed = (now - self.start_ts) as u128;
+    (self.amount as u128 * elapsed / self.duration as u128) as u64
+  }
+}
+
+// budget held in a vault that `recipient` earns at `rate` tokens per second
+#[account]
+pub struct Stream {
+  pub sender: Pubkey,
+  pub recipient: Pubkey,
+  pub mint: Pubkey,
+  pub id: u64,
+  pub rate: u64,
+  // total put in the vault, grows with `top_up`
+  pub deposited: u64,
+  pub withdrawn: u64,
+  // accrual is settled at every top up so a drained stream does not earn
+  // for the time it was empty
+  pub checkpoint_ts: i64,
+  pub checkpoint_accrued: u64,
+  pub bump: u8,
+  pub vault_bump: u8,
+}
+
+impl Stream {
+  pub const LEN: usize = 8 // internal discriminator
+    + 3 * 32 // PubKey
+    + 4 * 8 // u64
+    + 8 // i64
+    + 8 // u64
+    + 2; // u8
+
+  pub fn accrued(&self, now: i64) -> u64 {
+    let elapsed = now.saturating_sub(self.checkpoint_ts).max(0) as u128;
+    let accrued = self.checkpoint_accrued as u128 + elapsed * self.rate as u128;
+    accrued.min(self.deposited as u128) as u64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn grant(amount: u64, start_ts: i64, cliff_ts: i64, duration: u64) -> Grant {
+    Grant {
+      grantor: Pubkey::new_unique(),
+      beneficiary: Pubkey::new_unique(),
+      mint: Pubkey::new_unique(),
+      id: 0,
+      revoker: None,
+      amount,
+      claimed: 0,
+      start_ts,
+      cliff_ts,
+      duration,
+      revoked: false,
+      bump: 0,
+      vault_bump: 0,
+    }
+  }
+
+  fn stream(rate: u64, deposited: u64, checkpoint_ts: i64) -> Stream {
+    Stream {
+      sender: Pubkey::new_unique(),
+      recipient: Pubkey::new_unique(),
+      mint: Pubkey::new_unique(),
+      id: 0,
+      rate,
+      deposited,
+      withdrawn: 0,
+      checkpoint_ts,
+      checkpoint_accrued: 0,
+      bump: 0,
+      vault_bump: 0,
+    }
+  }
+
+  #[test]
+  fn grant_vests_nothing_before_the_cliff() {
+    let grant = grant(1_000, 100, 200, 1_000);
+    assert_eq!(grant.vested(50), 0);
+    assert_eq!(grant.vested(100), 0);
+    assert_eq!(grant.vested(199), 0);
+  }
+
+  #[test]
+  fn grant_releases_the_elapsed_share_at_the_cliff() {
+    let grant = grant(1_000, 100, 200, 1_000);
+    assert_eq!(grant.vested(200), 100);
+    assert_eq!(grant.vested(600), 500);
+  }
+
+  #[test]
+  fn grant_is_fully_vested_at_the_end() {
+    let grant = grant(1_000, 100, 200, 1_000);
+    assert_eq!(grant.vested(1_099), 999);
+    assert_eq!(grant.vested(1_100), 1_000);
+    assert_eq!(grant.vested(i64::MAX), 1_000);
+  }
+
+  #[test]
+  fn revoked_grant_keeps_what_had_vested() {
+    let mut grant = grant(1_000, 100, 200, 1_000);
+    grant.amount = grant.vested(600);
+    grant.revoked = true;
+    assert_eq!(grant.vested(600), 500);
+    assert_eq!(grant.vested(1_100), 500);
+  }
+
+  #[test]
+  fn stream_accrues_per_second_from_the_checkpoint() {
+    let stream = stream(10, 1_000, 100);
+    assert_eq!(stream.accrued(50), 0);
+    assert_eq!(stream.accrued(100), 0);
+    assert_eq!(stream.accrued(130), 300);
+  }
+
+  #[test]
+  fn stream_accrual_is_capped_by_the_deposit() {
+    let stream = stream(10, 1_000, 100);
+    assert_eq!(stream.accrued(200), 1_000);
+    assert_eq!(stream.accrued(i64::MAX), 1_000);
+  }
+
+  #[test]
+  fn top_up_after_draining_does_not_pay_for_the_empty_time() {
+    let mut stream = stream(10, 1_000, 100);
+    // drained at 200, topped up at 500 as `top_up` does
+    stream.checkpoint_accrued = stream.accrued(500);
+    stream.checkpoint_ts = 500;
+    stream.deposited += 1_000;
+    assert_eq!(stream.accrued(500), 1_000);
+    assert_eq!(stream.accrued(550), 1_500);
+    assert_eq!(stream.accrued(700), 2_000);
+  }
+}